@@ -0,0 +1,244 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use serde::{Serialize, Deserialize};
+use crate::*;
+use crate::check::{Problem, RecordKey};
+use crate::tombstone::Tombstone;
+use crate::transaction::abort;
+
+/// the archive format [`Database::export`] writes, bumped whenever [`Record`] changes incompatibly
+pub const ARCHIVE_FORMAT: u32 = 1;
+
+/// One line of an archive, written as ron; the first line is always a `Header`.
+/// Values are ron whatever [`Encoding`](crate::encoding::Encoding) the database uses,
+/// event and counter keys are the big endian bits of their [`EventId`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Record {
+    Header { format: u32 },
+    Item { id: ItemId, schema: u32, item: ItemData },
+    Event { key: u64, schema: u32, type_name: String, data: String },
+    /// the last event id handed out for a date
+    Counter { key: u64, count: u64 },
+    Deleted { id: ItemId, tombstone: Tombstone },
+}
+
+/// What an archive holds, and which of its type names the registry doesn't know
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    pub items: usize,
+    pub events: usize,
+    pub counters: usize,
+    pub deleted: usize,
+    pub unregistered: BTreeSet<String>,
+    /// records [`Database::export`] couldn't read and left out, as [`Database::check`] would report them
+    pub skipped: Vec<Problem>,
+}
+
+impl ArchiveSummary {
+    fn count(&mut self, db: &Database, record: &Record) {
+        let type_name = match record {
+            Record::Header { .. } => return,
+            Record::Item { item, .. } => {self.items += 1; &item.type_name}
+            Record::Event { type_name, .. } => {self.events += 1; type_name}
+            Record::Counter { .. } => {self.counters += 1; return}
+            Record::Deleted { tombstone, .. } => {self.deleted += 1; &tombstone.item.type_name}
+        };
+        if db.type_registry.get_with_name(db.migrations.resolve(type_name)).is_none() {
+            self.unregistered.insert(type_name.clone());
+        }
+    }
+}
+
+/// the problem behind an error reading `record`
+fn unreadable(record: RecordKey, e: DbError) -> Problem {
+    match e {
+        DbError::NoTypeName => Problem::MissingType(record),
+        DbError::NoData => Problem::MissingData(record),
+        DbError::FromUtf8Error(_) => Problem::BadTypeName(record),
+        e => Problem::Undecodable(record, e.to_string()),
+    }
+}
+
+fn key_bits(key: &[u8]) -> Result<u64, DbError> {
+    Ok(u64::from_be_bytes(key.try_into().map_err(|_| DbError::BadEventKey(key.len()))?))
+}
+
+/// calls `f` with every record after the header, checking the header on the way
+fn read_archive<R: BufRead>(reader: R, mut f: impl FnMut(Record) -> Result<(), DbError>) -> Result<(), DbError> {
+    let mut lines = reader.lines().enumerate();
+    let parse = |number: usize, line: std::io::Result<String>| -> Result<Record, DbError> {
+        ron::from_str(&line?).map_err(|e| DbError::BadArchive(number + 1, e.to_string()))
+    };
+    match lines.next() {
+        Some((number, line)) => match parse(number, line)? {
+            Record::Header { format } if format > ARCHIVE_FORMAT => return Err(DbError::ArchiveTooNew(format)),
+            Record::Header { .. } => {},
+            _ => return Err(DbError::BadArchive(1, "expected a header".into())),
+        },
+        None => return Err(DbError::BadArchive(1, "the archive is empty".into())),
+    }
+    for (number, line) in lines {
+        match parse(number, line)? {
+            Record::Header { .. } => return Err(DbError::BadArchive(number + 1, "a second header".into())),
+            record => f(record)?,
+        }
+    }
+    Ok(())
+}
+
+impl Database {
+    /// writes every item, event, event counter and deleted item to `writer` as an archive,
+    /// records written while it runs may or may not make it in;
+    /// a record that can't be read is left out and listed in [`ArchiveSummary::skipped`] instead of stopping the backup
+    pub fn export<W: Write>(&self, mut writer: W) -> Result<ArchiveSummary, DbError> {
+        let mut summary = ArchiveSummary::default();
+        let mut skipped = Vec::new();
+        let mut write = |record: Record| -> Result<(), DbError> {
+            summary.count(self, &record);
+            writeln!(writer, "{}", ron::to_string(&record)?)?;
+            Ok(())
+        };
+        write(Record::Header { format: ARCHIVE_FORMAT })?;
+        for key in self.items.iter().keys() {
+            let key = key?;
+            let Ok(id) = ItemId::try_from(key.as_ref()) else {
+                skipped.push(Problem::BadKey(key));
+                continue;
+            };
+            match self.get_item_data(id) {
+                Ok(item) => write(Record::Item { id, schema: self.schema_version(id)?, item })?,
+                Err(e) => skipped.push(unreadable(id.into(), e)),
+            }
+        }
+        for key in self.events.iter().keys() {
+            let key = key?;
+            let Ok(key) = EventId::try_from(key.as_ref()) else {
+                skipped.push(Problem::BadKey(key));
+                continue;
+            };
+            match self.get_event(key) {
+                Ok(event) => write(Record::Event { key: key_bits(key.as_ref())?, schema: self.schema_version(key)?, type_name: event.type_name, data: event.data })?,
+                Err(e) => skipped.push(unreadable(key.into(), e)),
+            }
+        }
+        for res in self.db.iter() {
+            let (key, count) = res?;
+            // the root tree only holds counters, apart from items written before the items tree existed
+            if key.len() != std::mem::size_of::<u64>() {continue}
+            // a corrupt counter is left out, Database::repair raises it from the events once imported
+            let Ok(count) = storage::decode_counter(&key, &count) else {continue};
+            write(Record::Counter { key: key_bits(&key)?, count })?;
+        }
+        for (id, tombstone) in self.list_deleted()? {
+            write(Record::Deleted { id, tombstone })?;
+        }
+        writer.flush()?;
+        summary.skipped = skipped;
+        Ok(summary)
+    }
+
+    /// reads an archive written by [`Database::export`] without writing anything,
+    /// fails on the first record that doesn't parse
+    pub fn validate_archive<R: BufRead>(&self, reader: R) -> Result<ArchiveSummary, DbError> {
+        let mut summary = ArchiveSummary::default();
+        read_archive(reader, |record| {
+            summary.count(self, &record);
+            Ok(())
+        })?;
+        Ok(summary)
+    }
+
+    /// writes every record of an archive into the database, replacing whatever is stored under the same keys;
+    /// each record is its own transaction so run [`Database::validate_archive`] first to avoid stopping half way
+    pub fn import<R: BufRead>(&self, reader: R) -> Result<ArchiveSummary, DbError> {
+        // dependencies may come later in the archive than the items pointing at them
+        let integrity = integrity::Integrity { check_dependencies: false, ..self.integrity };
+        let mut summary = ArchiveSummary::default();
+        read_archive(reader, |record| {
+            summary.count(self, &record);
+            self.transaction_with(integrity, None, |tx| {
+                match &record {
+                    Record::Header { .. } => {},
+                    Record::Item { id, schema, item } => {
+                        tx.insert_item_data(*id, item)?;
+                        tx.set_schema(&id.as_bytes()[..], *schema)?;
+                    }
+                    Record::Event { key, schema, type_name, data } => {
+                        let key = &key.to_be_bytes()[..];
+                        EventId::try_from(key).map_err(abort)?;
+                        tx.type_tree.insert(key, type_name.as_str())?;
+                        tx.events.insert(key, tx.encode(type_name, data)?)?;
+                        tx.set_schema(key, *schema)?;
+                    }
+                    Record::Counter { key, count } => {
+                        // never go back, or new events would reuse ids
+                        let key = &key.to_be_bytes()[..];
+                        // a corrupt counter gets replaced
+                        let current = tx.db.get(key)?.map_or(0, |raw| storage::decode_counter(key, &raw).unwrap_or(0));
+                        if *count > current {
+                            tx.db.insert(key, storage::encode_counter(*count))?;
+                        }
+                    }
+                    Record::Deleted { id, tombstone } => {
+                        tx.tombstones.insert(&id.as_bytes()[..], ron::to_string(tombstone).map_err(abort)?)?;
+                    }
+                }
+                Ok(())
+            })
+        })?;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{test_db, test_date};
+    #[test]
+    fn export_import() {
+        use crate::{ItemData, ItemId, EventData, DbError};
+        let db = test_db();
+        let id = ItemId::from_u128(1);
+        let item = ItemData { type_name: "crate".into(), data: "First".into() };
+        db.insert_item_data(id, &item).unwrap();
+        db.insert_item_data(ItemId::from_u128(2), &item).unwrap();
+        db.remove(ItemId::from_u128(2)).unwrap();
+        let event = db.add_event(&EventData { type_name: "note".into(), data: "\"line\\nbreak\"".into(), date: test_date(), time: None }).unwrap();
+        let mut archive = Vec::new();
+        let summary = db.export(&mut archive).unwrap();
+        assert_eq!((summary.items, summary.events, summary.counters, summary.deleted), (1, 1, 1, 1));
+        assert_eq!(archive.iter().filter(|&&b| b == b'\n').count(), 5);
+        let copy = test_db();
+        let checked = copy.validate_archive(&archive[..]).unwrap();
+        assert_eq!(checked, summary);
+        assert_eq!(checked.unregistered.into_iter().collect::<Vec<_>>(), vec!["crate".to_string(), "note".to_string()]);
+        assert!(copy.items.is_empty());
+        copy.import(&archive[..]).unwrap();
+        assert_eq!(copy.get_item_data(id).unwrap(), item);
+        assert_eq!(copy.get_event(event).unwrap().data, db.get_event(event).unwrap().data);
+        assert_eq!(copy.list_deleted().unwrap(), db.list_deleted().unwrap());
+        assert_eq!(copy.get_next_key(test_date()).unwrap(), event.with_id(2));
+        assert!(matches!(copy.validate_archive(&b"Counter(key:1,count:1)\n"[..]), Err(DbError::BadArchive(1, _))));
+        assert!(matches!(copy.validate_archive(&b"Header(format:99)\n"[..]), Err(DbError::ArchiveTooNew(99))));
+    }
+
+    #[test]
+    fn export_skips_unreadable() {
+        use crate::{ItemData, ItemId, check::{Problem, RecordKey}};
+        let db = test_db();
+        let item = ItemData { type_name: "crate".into(), data: "First".into() };
+        let (good, garbled, untyped) = (ItemId::from_u128(1), ItemId::from_u128(2), ItemId::from_u128(3));
+        db.insert_item_data(good, &item).unwrap();
+        db.type_tree.insert(garbled, "crate").unwrap();
+        db.items.insert(garbled, &[0xff, 0xfe][..]).unwrap();
+        db.items.insert(untyped, "()").unwrap();
+        let mut archive = Vec::new();
+        let summary = db.export(&mut archive).unwrap();
+        assert_eq!(summary.items, 1);
+        assert_eq!(summary.skipped.len(), 2);
+        assert!(summary.skipped.iter().any(|p| matches!(p, Problem::Undecodable(RecordKey::Item(id), _) if *id == garbled)));
+        assert!(summary.skipped.contains(&Problem::MissingType(RecordKey::Item(untyped))));
+        let copy = test_db();
+        copy.import(&archive[..]).unwrap();
+        assert_eq!(copy.get_item_data(good).unwrap(), item);
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use crate::*;
+use crate::transaction::TxResult;
+
+/// The key of a record in the items or events tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKey {
+    Item(ItemId),
+    Event(EventId),
+}
+
+impl RecordKey {
+    fn parse(key: &[u8]) -> Result<RecordKey, DbError> {
+        match ItemId::try_from(key) {
+            Ok(id) => Ok(RecordKey::Item(id)),
+            Err(_) => Ok(RecordKey::Event(EventId::try_from(key)?)),
+        }
+    }
+}
+
+impl From<ItemId> for RecordKey {
+    fn from(id: ItemId) -> Self {
+        RecordKey::Item(id)
+    }
+}
+
+impl From<EventId> for RecordKey {
+    fn from(key: EventId) -> Self {
+        RecordKey::Event(key)
+    }
+}
+
+impl AsRef<[u8]> for RecordKey {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            RecordKey::Item(id) => id.as_bytes(),
+            RecordKey::Event(key) => key.as_ref(),
+        }
+    }
+}
+
+/// Something [`Database::check`] found wrong, each of these would otherwise only show up as a [`DbError`] when read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// there is a type name but no data under the key
+    MissingData(RecordKey),
+    /// there is data but no type name, so nothing knows how to read it
+    MissingType(RecordKey),
+    /// the type name isn't in the registry, not even through an alias
+    UnregisteredType(RecordKey, String),
+    /// the data doesn't deserialize as its type, with the error it gave
+    Undecodable(RecordKey, String),
+    /// the item depends on an item that is not in the database
+    DanglingDependency(ItemId, ItemId),
+    /// a key in the types, items or events tree that is neither an [`ItemId`] nor an [`EventId`]
+    BadKey(Vec<u8>),
+    /// the type name isn't UTF-8
+    BadTypeName(RecordKey),
+    /// the item reads fine but its indexes can't be built, with the error it gave
+    Unindexable(ItemId, String),
+    /// the counter for a day is below an event id already used that day, so new events would overwrite old ones
+    CounterBehind { date: Date, counter: u64, highest: u64 },
+}
+
+/// How far [`Database::repair`] goes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepairPolicy {
+    /// only fixes that lose nothing: type names without data are dropped and counters are raised
+    #[default]
+    Conservative,
+    /// also removes what can't be read: data without a type name, bad keys and type names and records that don't deserialize,
+    /// undecodable items go to the tombstones so they can still be looked at
+    RemoveUnreadable,
+}
+
+/// What [`Database::repair`] did, unregistered types, dangling dependencies and unindexable items always need a person
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    pub fixed: Vec<Problem>,
+    pub remaining: Vec<Problem>,
+}
+
+impl<'a> DbTransaction<'a> {
+    /// drops whatever is stored under `record` without going through history or tombstones
+    fn remove_raw(&self, record: RecordKey) -> TxResult<()> {
+        let key = record.as_ref();
+        let type_name = self.type_tree.remove(key)?;
+        self.set_schema(key, 0)?;
+        match record {
+            RecordKey::Item(id) => {
+                self.items.remove(key)?;
+                if let Some(type_name) = type_name {
+                    self.type_index.remove(index::type_key(&type_name, id))?;
+                }
+            }
+            RecordKey::Event(_) => {
+                self.events.remove(key)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Database {
+    /// walks every tree looking for records that can't be read back, see [`Problem`]
+    pub fn check(&self) -> Result<Vec<Problem>, DbError> {
+        let mut problems = Vec::new();
+        for res in self.type_tree.iter() {
+            let (key, type_name) = res?;
+            let Ok(record) = RecordKey::parse(&key) else {
+                problems.push(Problem::BadKey(key));
+                continue;
+            };
+            let data = match record {
+                RecordKey::Item(_) => self.items.get(&key)?,
+                RecordKey::Event(_) => self.events.get(&key)?,
+            };
+            let Some(data) = data else {
+                problems.push(Problem::MissingData(record));
+                continue;
+            };
+            let Ok(type_name) = String::from_utf8(type_name) else {
+                problems.push(Problem::BadTypeName(record));
+                continue;
+            };
+            if self.type_registry.get_with_name(self.migrations.resolve(&type_name)).is_none() {
+                problems.push(Problem::UnregisteredType(record, type_name));
+                continue;
+            }
+            if let Err(e) = self.deserialize_obj(record, &type_name, &data) {
+                problems.push(Problem::Undecodable(record, e.to_string()));
+                continue;
+            }
+            if let RecordKey::Item(id) = record {
+                let indexes = self.data_to_ron(record, &type_name, &data)
+                    .and_then(|data| index::item_indexes(&self.type_registry, &self.migrations, type_name.as_bytes(), data.as_bytes(), id));
+                let indexes = match indexes {
+                    Ok(indexes) => indexes,
+                    Err(e) => {
+                        problems.push(Problem::Unindexable(id, e.to_string()));
+                        continue;
+                    }
+                };
+                for dep in indexes.dependencies {
+                    if self.items.get(dep)?.is_none() {
+                        problems.push(Problem::DanglingDependency(id, dep));
+                    }
+                }
+            }
+        }
+        for tree in [&self.items, &self.events] {
+            for key in tree.iter().keys() {
+                let key = key?;
+                if self.type_tree.get(&key)?.is_none() {
+                    problems.push(match RecordKey::parse(&key) {
+                        Ok(record) => Problem::MissingType(record),
+                        Err(_) => Problem::BadKey(key),
+                    });
+                }
+            }
+        }
+        let mut highest = BTreeMap::new();
+        for key in self.events.iter().keys() {
+            // a bad key was reported above
+            let Ok(key) = EventId::try_from(key?.as_ref()) else {continue};
+            let day = highest.entry(key.date_from_key().0).or_insert(0);
+            *day = key.val().max(*day);
+        }
+        for (date, highest) in highest {
+            let date = Date(date);
+            let key = EventId::date_key(date);
+            // a corrupt counter is as good as none, repairing overwrites it
+            let counter = self.db.get(key)?.map_or(0, |raw| storage::decode_counter(key.as_ref(), &raw).unwrap_or(0));
+            if counter < highest {
+                problems.push(Problem::CounterBehind { date, counter, highest });
+            }
+        }
+        Ok(problems)
+    }
+
+    /// fixes what [`Database::check`] finds as far as `policy` allows
+    pub fn repair(&self, policy: RepairPolicy) -> Result<RepairReport, DbError> {
+        let remove_unreadable = policy == RepairPolicy::RemoveUnreadable;
+        let mut report = RepairReport::default();
+        for problem in self.check()? {
+            let fixed = match &problem {
+                Problem::MissingData(record) => {
+                    self.transaction(|tx| tx.remove_raw(*record))?;
+                    true
+                }
+                Problem::CounterBehind { date, highest, .. } => {
+                    self.transaction(|tx| {
+                        let key = EventId::date_key(*date);
+                        if tx.db.get(key)?.map_or(0, |raw| storage::decode_counter(key.as_ref(), &raw).unwrap_or(0)) < *highest {
+                            tx.db.insert(key, storage::encode_counter(*highest))?;
+                        }
+                        Ok(())
+                    })?;
+                    true
+                }
+                Problem::BadKey(key) if remove_unreadable => {
+                    self.transaction(|tx| {
+                        for tree in [&tx.type_tree, &tx.items, &tx.events] {
+                            tree.remove(&key[..])?;
+                        }
+                        tx.set_schema(key, 0)?;
+                        Ok(())
+                    })?;
+                    true
+                }
+                Problem::MissingType(record) | Problem::BadTypeName(record) | Problem::Undecodable(record @ RecordKey::Event(_), _) if remove_unreadable => {
+                    self.transaction(|tx| tx.remove_raw(*record))?;
+                    true
+                }
+                // an item that is still depended on stays put if the remove policy says so
+                Problem::Undecodable(RecordKey::Item(id), _) if remove_unreadable => self.remove(*id).is_ok(),
+                _ => false,
+            };
+            if fixed {
+                report.fixed.push(problem);
+            } else {
+                report.remaining.push(problem);
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use crate::test::{test_db, test_date};
+    #[test]
+    fn check_and_repair() {
+        use crate::{ItemData, ItemId, EventId, check::{Problem, RecordKey, RepairPolicy}, plants::{Plant, PlantTypes}};
+        let mut db = test_db();
+        db.type_registry.register::<Plant>();
+        db.type_registry.register::<PlantTypes>();
+        let plant = db.insert(Plant::test(0)).unwrap();
+        let event = db.add_event_obj(&Plant::test(0), test_date()).unwrap();
+        db.db.remove(EventId::date_key(test_date())).unwrap();
+        let (no_data, no_type, unknown, garbled) = (ItemId::from_u128(1), ItemId::from_u128(2), ItemId::from_u128(3), ItemId::from_u128(4));
+        db.type_tree.insert(no_data, "anything").unwrap();
+        db.items.insert(no_type, "()").unwrap();
+        db.insert_item_data(unknown, &ItemData { type_name: "unknown".into(), data: "()".into() }).unwrap();
+        db.type_tree.insert(garbled, std::any::type_name::<Plant>()).unwrap();
+        db.items.insert(garbled, "not a plant").unwrap();
+        let problems = db.check().unwrap();
+        assert_eq!(problems.len(), 6);
+        assert!(problems.contains(&Problem::MissingData(RecordKey::Item(no_data))));
+        assert!(problems.contains(&Problem::MissingType(RecordKey::Item(no_type))));
+        assert!(problems.contains(&Problem::UnregisteredType(RecordKey::Item(unknown), "unknown".into())));
+        assert!(problems.iter().any(|p| matches!(p, Problem::Undecodable(RecordKey::Item(id), _) if *id == garbled)));
+        assert!(problems.contains(&Problem::DanglingDependency(plant, ItemId::from_u128(1001))));
+        assert!(problems.contains(&Problem::CounterBehind { date: test_date(), counter: 0, highest: event.val() }));
+        let report = db.repair(RepairPolicy::Conservative).unwrap();
+        assert_eq!((report.fixed.len(), report.remaining.len()), (2, 4));
+        assert_eq!(db.get_next_key(test_date()).unwrap(), event.with_id(2));
+        let report = db.repair(RepairPolicy::RemoveUnreadable).unwrap();
+        assert_eq!((report.fixed.len(), report.remaining.len()), (2, 2));
+        assert_eq!(db.list_deleted().unwrap()[0].0, garbled);
+        assert_eq!(db.check().unwrap(), report.remaining);
+    }
+
+    #[test]
+    fn check_keeps_walking() {
+        use bevy_reflect::prelude::*;
+        use serde::{Serialize, Deserialize};
+        use crate::{ItemId, EventId, index::{Indexed, ReflectIndexed}, check::{Problem, RecordKey, RepairPolicy}};
+        #[derive(Reflect, Default, Serialize, Deserialize)]
+        #[reflect(Deserialize, Serialize, Indexed)]
+        struct Misindexed {
+            name: String,
+        }
+        impl Indexed for Misindexed {
+            const INDEXED_FIELDS: &'static [&'static str] = &["nmae"];
+        }
+        let mut db = test_db();
+        db.type_registry.register::<Misindexed>();
+        let (bad_name, misindexed) = (ItemId::from_u128(1), ItemId::from_u128(2));
+        db.type_tree.insert(&b"short"[..], "anything").unwrap();
+        db.events.insert(&b"tiny"[..], "()").unwrap();
+        db.type_tree.insert(bad_name, &[0xff, 0xfe][..]).unwrap();
+        db.items.insert(bad_name, "()").unwrap();
+        db.type_tree.insert(misindexed, std::any::type_name::<Misindexed>()).unwrap();
+        db.items.insert(misindexed, "(name: \"a\")").unwrap();
+        let event = EventId::date_key(test_date()).with_id(1);
+        db.type_tree.insert(event, "anything").unwrap();
+        let problems = db.check().unwrap();
+        assert_eq!(problems.len(), 5);
+        assert!(problems.contains(&Problem::BadKey(b"short".to_vec())));
+        assert!(problems.contains(&Problem::BadKey(b"tiny".to_vec())));
+        assert!(problems.contains(&Problem::BadTypeName(RecordKey::Item(bad_name))));
+        assert!(problems.iter().any(|p| matches!(p, Problem::Unindexable(id, _) if *id == misindexed)));
+        assert!(problems.contains(&Problem::MissingData(RecordKey::Event(event))));
+        let report = db.repair(RepairPolicy::RemoveUnreadable).unwrap();
+        assert_eq!(report.remaining.len(), 1);
+        assert_eq!(db.check().unwrap(), report.remaining);
+    }
+
+    #[test]
+    fn corrupt_records() {
+        use crate::{ItemId, EventId, DbError, check::RecordKey, plants::{Plant, PlantTypes}};
+        let mut db = test_db();
+        db.type_registry.register::<Plant>();
+        db.type_registry.register::<PlantTypes>();
+        let id = ItemId::from_u128(1);
+        db.type_tree.insert(id, std::any::type_name::<Plant>()).unwrap();
+        db.items.insert(id, "not a plant").unwrap();
+        assert!(matches!(db.get_obj(id), Err(DbError::Deserialize(_, RecordKey::Item(key), _)) if key == id));
+        assert!(matches!(db.get::<Plant>(id), Err(DbError::Deserialize(..))));
+        db.db.insert(EventId::date_key(test_date()), [0xff; 3]).unwrap();
+        assert!(matches!(db.add_event_obj(&Plant::test(0), test_date()), Err(DbError::CorruptCounter(_))));
+    }
+}
@@ -128,8 +128,13 @@ impl PartialEq for CallbackReg {
 
 impl CallbackReg {
     pub fn new() -> CallbackReg {
+        CallbackReg::with_modules(&crate::module::Modules::builtin())
+    }
+
+    /// a registry that knows the types of `modules`, use the same set the server's [`crate::Database`] was built from
+    pub fn with_modules(modules: &crate::module::Modules) -> CallbackReg {
         CallbackReg{
-            type_reg: Rc::new(crate::type_registry()),
+            type_reg: Rc::new(modules.type_registry()),
             loaded_items: Default::default(),
             reg: Default::default(),
             next: Default::default(),
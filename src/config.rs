@@ -0,0 +1,209 @@
+#[cfg(feature = "sled")]
+use std::path::{Path, PathBuf};
+use crate::*;
+use crate::encoding::Encoding;
+use crate::integrity::Integrity;
+use crate::migration::Migrations;
+use crate::module::Modules;
+use crate::storage::{MemoryBackend, ReadOnlyBackend, StorageBackend};
+#[cfg(feature = "sled")]
+use crate::storage::SledBackend;
+
+enum Location {
+    #[cfg(feature = "sled")]
+    Path(PathBuf),
+    /// a sled database in a temporary directory, removed when it is dropped
+    #[cfg(feature = "sled")]
+    Temporary,
+    Memory,
+}
+
+/// Everything [`Database::new`] decides for you, build one up and [`DatabaseConfig::open`] it
+pub struct DatabaseConfig {
+    location: Location,
+    #[cfg(feature = "sled")]
+    cache_capacity: Option<u64>,
+    #[cfg(feature = "sled")]
+    flush_every_ms: Option<Option<u64>>,
+    read_only: bool,
+    type_registry: Option<bevy_reflect::TypeRegistry>,
+    integrity: Integrity,
+    migrations: Migrations,
+    encoding: Option<Encoding>,
+}
+
+impl DatabaseConfig {
+    /// a sled database at `path`
+    #[cfg(feature = "sled")]
+    pub fn new<P: AsRef<Path>>(path: P) -> DatabaseConfig {
+        DatabaseConfig::at(Location::Path(path.as_ref().to_path_buf()))
+    }
+
+    /// a sled database that is deleted when the [`Database`] is dropped
+    #[cfg(feature = "sled")]
+    pub fn temporary() -> DatabaseConfig {
+        DatabaseConfig::at(Location::Temporary)
+    }
+
+    /// a [`MemoryBackend`], nothing touches the disk; sled settings are ignored
+    pub fn in_memory() -> DatabaseConfig {
+        DatabaseConfig::at(Location::Memory)
+    }
+
+    fn at(location: Location) -> DatabaseConfig {
+        DatabaseConfig {
+            location,
+            #[cfg(feature = "sled")]
+            cache_capacity: None,
+            #[cfg(feature = "sled")]
+            flush_every_ms: None,
+            read_only: false,
+            type_registry: None,
+            integrity: Integrity::default(),
+            migrations: Migrations::new(),
+            encoding: None,
+        }
+    }
+
+    /// how many bytes sled may keep cached, sled's own default otherwise
+    #[cfg(feature = "sled")]
+    pub fn cache_capacity(mut self, bytes: u64) -> Self {
+        self.cache_capacity = Some(bytes);
+        self
+    }
+
+    /// how often sled flushes to disk, `None` only flushes when asked to
+    #[cfg(feature = "sled")]
+    pub fn flush_every_ms(mut self, every: Option<u64>) -> Self {
+        self.flush_every_ms = Some(every);
+        self
+    }
+
+    /// every write fails with [`DbError::ReadOnly`] and no tree is created, for reporting jobs that must not change anything;
+    /// a path that doesn't exist is an [`DbError::IoError`] instead of a new database.
+    /// sled still takes its directory lock, so this can't open a database a running server has open;
+    /// point it at a copy of the directory taken while the server is stopped, or import a [`Database::export`] archive into a new one
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// the types the database can (de)serialize, start from [`type_registry`] to keep the built in ones
+    pub fn type_registry(mut self, type_registry: bevy_reflect::TypeRegistry) -> Self {
+        self.type_registry = Some(type_registry);
+        self
+    }
+
+    /// registers the types of `modules` instead of the built in ones, the same as [`DatabaseConfig::type_registry`]
+    /// with [`Modules::type_registry`]
+    pub fn modules(self, modules: &Modules) -> Self {
+        self.type_registry(modules.type_registry())
+    }
+
+    pub fn integrity(mut self, integrity: Integrity) -> Self {
+        self.integrity = integrity;
+        self
+    }
+
+    pub fn migrations(mut self, migrations: Migrations) -> Self {
+        self.migrations = migrations;
+        self
+    }
+
+    /// the encoding for a new database, an existing one has to match it, see [`Database::set_encoding`]
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    #[cfg(feature = "sled")]
+    fn sled(&self) -> sled::Config {
+        let mut config = sled::Config::new();
+        match &self.location {
+            Location::Path(path) => config = config.path(path),
+            Location::Temporary => config = config.temporary(true),
+            Location::Memory => {},
+        }
+        if let Some(bytes) = self.cache_capacity {
+            config = config.cache_capacity(bytes);
+        }
+        if let Some(every) = self.flush_every_ms {
+            config = config.flush_every_ms(every);
+        }
+        config
+    }
+
+    pub fn open(self) -> Result<Database, DbError> {
+        #[cfg(feature = "sled")]
+        if let (true, Location::Path(path)) = (self.read_only, &self.location) {
+            if !path.exists() {
+                return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("no database at {}", path.display())).into());
+            }
+        }
+        let backend: Box<dyn StorageBackend> = match self.location {
+            Location::Memory => Box::new(MemoryBackend::new()),
+            #[cfg(feature = "sled")]
+            _ => Box::new(SledBackend::from(self.sled().open()?)),
+        };
+        let backend: Box<dyn StorageBackend> = if self.read_only {Box::new(ReadOnlyBackend(backend))} else {backend};
+        let mut db = Database::with_backend(backend, self.type_registry.unwrap_or_else(type_registry))?;
+        db.set_integrity(self.integrity);
+        db.set_migrations(self.migrations)?;
+        if let Some(encoding) = self.encoding {
+            db.set_encoding(encoding)?;
+        }
+        Ok(db)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "sled")]
+    #[test]
+    fn config() {
+        use crate::{ItemData, ItemId, DbError, config::DatabaseConfig, encoding::Encoding, plants::{Plant, PlantTypes}};
+        let mut registry = crate::type_registry();
+        registry.register::<Plant>();
+        registry.register::<PlantTypes>();
+        let db = DatabaseConfig::temporary().cache_capacity(1 << 20).flush_every_ms(None).type_registry(registry).encoding(Encoding::Bincode).open().unwrap();
+        let plant = db.put(&Plant::test(0)).unwrap();
+        assert_eq!(db.get::<Plant>(plant).unwrap(), Plant::test(0));
+        let dir = std::env::temp_dir().join(format!("server_lib_config_{}", ItemId::new().0));
+        let (source, copy) = (dir.join("source"), dir.join("copy"));
+        assert!(matches!(DatabaseConfig::new(&copy).read_only(true).open(), Err(DbError::IoError(_))));
+        assert!(!copy.exists());
+        let id = ItemId::from_u128(1);
+        let item = ItemData { type_name: "test".into(), data: "1".into() };
+        // a database from before most trees existed, copied so no other handle holds the one opened read only
+        let sled = sled::open(&source).unwrap();
+        sled.open_tree("types").unwrap().insert(id.as_bytes(), "test").unwrap();
+        sled.open_tree("items").unwrap().insert(id.as_bytes(), "1").unwrap();
+        sled.open_tree("meta").unwrap().insert("index_format", "1").unwrap();
+        sled.flush().unwrap();
+        fn copy_dir(from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+            std::fs::create_dir_all(to)?;
+            for entry in std::fs::read_dir(from)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    copy_dir(&entry.path(), &to.join(entry.file_name()))?;
+                } else {
+                    std::fs::copy(entry.path(), to.join(entry.file_name()))?;
+                }
+            }
+            Ok(())
+        }
+        copy_dir(&source, &copy).unwrap();
+        drop(sled);
+        let db = DatabaseConfig::new(&copy).read_only(true).open().unwrap();
+        assert_eq!(db.get_item_data(id).unwrap(), item);
+        assert!(db.history(id).unwrap().is_empty());
+        assert!(matches!(db.insert_item_data(id, &item), Err(DbError::ReadOnly)));
+        assert!(matches!(db.remove(id), Err(DbError::ReadOnly)));
+        assert!(matches!(db.type_index.clear(), Err(DbError::ReadOnly)));
+        let mut trees = db.backend.tree_names().unwrap();
+        trees.sort();
+        assert_eq!(trees, [crate::storage::DEFAULT_TREE, "items", "meta", "types"]);
+        drop(db);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
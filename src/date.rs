@@ -1,35 +1,81 @@
 use serde::{Serialize, Deserialize};
 use bevy_reflect::prelude::*;
+use thiserror::Error;
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Reflect, FromReflect)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Error)]
+pub enum DateError {
+    #[error("Month {0} is not between 1 and 12")]
+    BadMonth(u8),
+    #[error("{year}/{month} has no day {day}")]
+    BadDay { year: i16, month: u8, day: u8 },
+    #[error("{year} has no ISO week {week}")]
+    BadWeek { year: i16, week: u8 },
+    #[error("Seasons have to start in the order spring, summer, autumn, winter")]
+    SeasonsOutOfOrder,
+    #[error("{hour:02}:{minute:02}:{second:02} is not a time of day")]
+    BadTime { hour: u32, minute: u32, second: u32 },
+    #[error("Year {0} does not fit in a Date")]
+    YearOutOfRange(i32),
+    #[error("{0}")]
+    Parse(&'static str),
+}
+
+/// Displays and parses as ISO 8601 (`2022-09-01`), parsing also takes `2022/9/1`;
+/// ordered by the packed value, which is calendar order for years from 0 on
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Reflect, FromReflect)]
 #[reflect_value(Serialize)]
 pub struct Date(pub(crate) u32);
 impl std::str::FromStr for Date {
-    type Err = &'static str;
+    type Err = DateError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         #[cfg(feature="yew")]
         web_sys::console::log_1(&s.into());
+        // a leading sign is part of the year, ISO 8601 writes years before 0 as -0001
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
         let mut segs = if s.contains("-") {s.split('-')} else {s.split('/')};
-        let year = if let Some(v) = segs.next() {match v.parse() {
-            Ok(v) => v,
-            Err(_) => return Err("Failed to parse year")
-        }} else {return Err("No Year Seg");};
+        let year = if let Some(v) = segs.next() {match v.parse::<i16>() {
+            Ok(v) => if negative {-v} else {v},
+            Err(_) => return Err(DateError::Parse("Failed to parse year"))
+        }} else {return Err(DateError::Parse("No Year Seg"));};
         let month = if let Some(v) = segs.next() {match v.parse() {
             Ok(v) => v,
-            Err(_) => return Err("Failed to parse month")
-        }} else {return Err("No Month Seg");};
+            Err(_) => return Err(DateError::Parse("Failed to parse month"))
+        }} else {return Err(DateError::Parse("No Month Seg"));};
         let day = if let Some(v) = segs.next() {match v.parse() {
             Ok(v) => v,
-            Err(_) => return Err("Failed to parse day")
-        }} else {return Err("No day Seg");};
-        Ok(Date::new_ymd(year, month, day))
+            Err(_) => return Err(DateError::Parse("Failed to parse day"))
+        }} else {return Err(DateError::Parse("No day Seg"));};
+        if segs.next().is_some() {
+            return Err(DateError::Parse("Too many Segs"));
+        }
+        Date::try_new_ymd(year, month, day)
     }
 }
 
 impl Date {
+    /// panics on a date that doesn't exist, use [`Date::try_new_ymd`] for anything that isn't a literal
     pub fn new_ymd(year: i16, month: u8, day: u8) -> Date {
-        assert!(day <= if month == 2 {29} else {days_in_month(month)} && day > 0);
-        assert!(month > 0 && month <= 12);
+        match Date::try_new_ymd(year, month, day) {
+            Ok(date) => date,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_new_ymd(year: i16, month: u8, day: u8) -> Result<Date, DateError> {
+        if !(1..=12).contains(&month) {
+            return Err(DateError::BadMonth(month));
+        }
+        if day == 0 || day > days_in_month(year, month) {
+            return Err(DateError::BadDay { year, month, day });
+        }
+        Ok(Date::from_ymd(year, month, day))
+    }
+
+    // the caller has checked the date exists
+    const fn from_ymd(year: i16, month: u8, day: u8) -> Date {
         let mut val = (year as u32) << 4;
         val += month as u32;
         val <<= 5;
@@ -54,10 +100,7 @@ impl Date {
         let mut month = self.month();
         let mut year = self.year();
         day += 1;
-        if is_leap_year(year) && month == 2 && day > 29 {
-            day = 1;
-            month += 1;
-        } else if day > days_in_month(month) && !(is_leap_year(year) && month == 2) {
+        if day > days_in_month(year, month) {
             day = 1;
             month += 1;
         }
@@ -65,7 +108,7 @@ impl Date {
             month = 1;
             year += 1;
         }
-        Date::new_ymd(year, month, day)
+        Date::from_ymd(year, month, day)
     }
 
     pub fn prev(&self) -> Date {
@@ -75,24 +118,395 @@ impl Date {
         day -= 1;
         if day == 0 {
             month -= 1;
-            if is_leap_year(year) && month == 2 {
-                day = 29;
-            } else {
-                day = days_in_month(month);
+            if month == 0 {
+                year -= 1;
+                month = 12;
             }
+            day = days_in_month(year, month);
         }
-        if month == 0 {
-            year -= 1;
-            month = 12;
-        }
-        Date::new_ymd(year, month, day)
+        Date::from_ymd(year, month, day)
     }
 
+    /// the same as `to_string`, which is what html date inputs take
     pub fn to_web_string(&self) -> String {
-        format!("{}-{}-{}", self.year(), self.month(), self.day())
+        self.to_string()
+    }
+
+    /// the current date in `tz`
+    pub fn today<Tz: chrono::TimeZone>(tz: &Tz) -> Date {
+        Date::try_from(chrono::Utc::now().with_timezone(tz).naive_local().date()).expect("the current year to fit in a Date")
+    }
+
+    /// moves by whole months, a day past the end of the new month becomes its last day;
+    /// [`DateError::YearOutOfRange`] if that leaves the years a Date can hold
+    pub fn add_months(&self, months: i32) -> Result<Date, DateError> {
+        self.shift_months(months as i64)
+    }
+
+    /// Feb 29 becomes Feb 28 outside leap years
+    pub fn add_years(&self, years: i32) -> Result<Date, DateError> {
+        self.shift_months(years as i64 * 12)
+    }
+
+    fn shift_months(&self, months: i64) -> Result<Date, DateError> {
+        let months = self.year() as i64 * 12 + self.month() as i64 - 1 + months;
+        let year = months.div_euclid(12);
+        let year = i16::try_from(year).map_err(|_| DateError::YearOutOfRange(year.clamp(i32::MIN as i64, i32::MAX as i64) as i32))?;
+        let month = months.rem_euclid(12) as u8 + 1;
+        Ok(Date::from_ymd(year, month, self.day().min(days_in_month(year, month))))
+    }
+
+    /// `days` later (earlier if negative), [`DateError::YearOutOfRange`] instead of the panic `+` gives past the years a Date can hold
+    pub fn checked_add_days(&self, days: i32) -> Result<Date, DateError> {
+        Date::from_days(self.days() as i64 + days as i64)
+    }
+
+    // days since 1970-01-01, after Howard Hinnant's days_from_civil
+    fn days(&self) -> i32 {
+        let month = self.month() as i32;
+        let year = self.year() as i32 - (month <= 2) as i32;
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let day_of_year = (153 * (if month > 2 {month - 3} else {month + 9}) + 2) / 5 + self.day() as i32 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    fn from_days(days: i64) -> Result<Date, DateError> {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {shifted_month + 3} else {shifted_month - 9};
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        let year = i16::try_from(year).map_err(|_| DateError::YearOutOfRange(year.clamp(i32::MIN as i64, i32::MAX as i64) as i32))?;
+        Ok(Date::from_ymd(year, month as u8, day as u8))
+    }
+
+    // 0 is Monday, 1970-01-01 was a Thursday
+    fn days_from_monday(&self) -> i32 {
+        (self.days() + 3).rem_euclid(7)
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        WEEKDAYS[self.days_from_monday() as usize]
+    }
+
+    /// 1 for January 1st
+    pub fn day_of_year(&self) -> u16 {
+        (*self - Date::from_ymd(self.year(), 1, 1)) as u16 + 1
+    }
+
+    /// the ISO 8601 week-numbering year and week, around new year the year can differ from [`Date::year`]
+    pub fn iso_week(&self) -> (i16, u8) {
+        // a week belongs to the year its Thursday is in
+        let thursday = *self + (3 - self.days_from_monday());
+        (thursday.year(), ((thursday.day_of_year() - 1) / 7 + 1) as u8)
+    }
+
+    /// the day of ISO week `week` of `year` (as [`Date::iso_week`] counts them) that falls on `weekday`
+    pub fn from_iso_week(year: i16, week: u8, weekday: Weekday) -> Result<Date, DateError> {
+        // week 1 is the week with January 4th in it, and the 28th of December is always in the last week
+        if week == 0 || week > Date::from_ymd(year, 12, 28).iso_week().1 {
+            return Err(DateError::BadWeek { year, week });
+        }
+        let jan_4 = Date::from_ymd(year, 1, 4);
+        Ok(jan_4 - jan_4.days_from_monday() + (week as i32 - 1) * 7 + weekday as i32)
+    }
+
+    pub fn season(&self, seasons: &Seasons) -> Season {
+        seasons.season_of(*self)
+    }
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Monday,
+    Weekday::Tuesday,
+    Weekday::Wednesday,
+    Weekday::Thursday,
+    Weekday::Friday,
+    Weekday::Saturday,
+    Weekday::Sunday,
+];
+
+/// Weeks start on Monday as in ISO 8601
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// 1 for Monday to 7 for Sunday
+    pub fn number_from_monday(self) -> u8 {
+        self as u8 + 1
+    }
+}
+
+impl std::fmt::Display for Weekday {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    /// the season on the other hemisphere at the same time
+    pub fn opposite(self) -> Season {
+        match self {
+            Season::Spring => Season::Autumn,
+            Season::Summer => Season::Winter,
+            Season::Autumn => Season::Spring,
+            Season::Winter => Season::Summer,
+        }
+    }
+}
+
+impl std::fmt::Display for Season {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hemisphere {
+    #[default]
+    Northern,
+    Southern,
+}
+
+/// Where the seasons start in the calendar, [`Seasons::default`] are the meteorological
+/// seasons of the northern hemisphere
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Seasons {
+    hemisphere: Hemisphere,
+    /// (month, day) of the first day of spring, summer, autumn and winter in the northern hemisphere;
+    /// in the southern one the same days start autumn, winter, spring and summer
+    starts: [(u8, u8); 4],
+}
+
+impl Default for Seasons {
+    fn default() -> Self {
+        Seasons::meteorological(Hemisphere::Northern)
+    }
+}
+
+impl Seasons {
+    /// the starts are (month, day) of the first day of spring, summer, autumn and winter as the northern
+    /// hemisphere has them, a start on Feb 29 moves to Feb 28 outside leap years
+    pub fn new(hemisphere: Hemisphere, starts: [(u8, u8); 4]) -> Result<Seasons, DateError> {
+        for &(month, day) in &starts {
+            Date::try_new_ymd(2000, month, day)?;
+        }
+        if starts.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(DateError::SeasonsOutOfOrder);
+        }
+        Ok(Seasons { hemisphere, starts })
+    }
+
+    /// whole months, starting March, June, September and December
+    pub fn meteorological(hemisphere: Hemisphere) -> Seasons {
+        Seasons { hemisphere, starts: [(3, 1), (6, 1), (9, 1), (12, 1)] }
+    }
+
+    /// from the equinoxes and solstices, which move by a day or so between years
+    pub fn astronomical(hemisphere: Hemisphere) -> Seasons {
+        Seasons { hemisphere, starts: [(3, 20), (6, 21), (9, 22), (12, 21)] }
+    }
+
+    pub fn hemisphere(&self) -> Hemisphere {
+        self.hemisphere
+    }
+
+    fn for_hemisphere(&self, season: Season) -> Season {
+        match self.hemisphere {
+            Hemisphere::Northern => season,
+            Hemisphere::Southern => season.opposite(),
+        }
+    }
+
+    fn start_in(&self, year: i16, northern: Season) -> Date {
+        let (month, day) = self.starts[northern as usize];
+        Date::from_ymd(year, month, day.min(days_in_month(year, month)))
+    }
+
+    pub fn season_of(&self, date: Date) -> Season {
+        let northern = [Season::Winter, Season::Autumn, Season::Summer, Season::Spring].into_iter()
+            .find(|&season| self.start_in(date.year(), season) <= date)
+            .unwrap_or(Season::Winter);
+        self.for_hemisphere(northern)
+    }
+
+    /// the days of `season` that starts in `year`, winter runs into the next year
+    pub fn range(&self, season: Season, year: i16) -> DateRange {
+        let northern = self.for_hemisphere(season);
+        let start = self.start_in(year, northern);
+        let end = match northern {
+            Season::Spring => self.start_in(year, Season::Summer),
+            Season::Summer => self.start_in(year, Season::Autumn),
+            Season::Autumn => self.start_in(year, Season::Winter),
+            Season::Winter => self.start_in(year + 1, Season::Spring),
+        };
+        DateRange::new(start, end - 1)
+    }
+}
+
+/// panics past the years a Date can hold, like integer overflow; [`Date::checked_add_days`] doesn't
+impl std::ops::Add<i32> for Date {
+    type Output = Date;
+    fn add(self, days: i32) -> Date {
+        match self.checked_add_days(days) {
+            Ok(date) => date,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// panics past the years a Date can hold, like integer overflow
+impl std::ops::Sub<i32> for Date {
+    type Output = Date;
+    fn sub(self, days: i32) -> Date {
+        match Date::from_days(self.days() as i64 - days as i64) {
+            Ok(date) => date,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// the number of days from `rhs` to `self`
+impl std::ops::Sub<Date> for Date {
+    type Output = i32;
+    fn sub(self, rhs: Date) -> i32 {
+        self.days() - rhs.days()
+    }
+}
+
+/// The days from `start` to `end`, both inclusive like [`Database::events_between`](crate::Database::events_between);
+/// empty when `end` is before `start`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DateRange {
+    start: Date,
+    end: Date,
+}
+
+impl DateRange {
+    pub fn new(start: Date, end: Date) -> DateRange {
+        DateRange { start, end }
+    }
+
+    /// `days` days starting at `start`
+    pub fn days_from(start: Date, days: i32) -> DateRange {
+        DateRange::new(start, start + (days - 1))
+    }
+
+    /// the calendar month `date` is in
+    pub fn month_of(date: Date) -> DateRange {
+        let start = Date::from_ymd(date.year(), date.month(), 1);
+        DateRange::new(start, Date::from_ymd(date.year(), date.month(), days_in_month(date.year(), date.month())))
+    }
+
+    /// the Monday to Sunday week `date` is in
+    pub fn week_of(date: Date) -> DateRange {
+        DateRange::days_from(date - date.days_from_monday(), 7)
+    }
+
+    pub fn start(&self) -> Date {
+        self.start
+    }
+
+    pub fn end(&self) -> Date {
+        self.end
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end < self.start
+    }
+
+    /// the number of days in the range
+    pub fn len(&self) -> usize {
+        if self.is_empty() {0} else {(self.end - self.start) as usize + 1}
+    }
+
+    pub fn contains(&self, date: Date) -> bool {
+        self.start <= date && date <= self.end
+    }
+
+    pub fn iter(&self) -> DateIter {
+        DateIter(*self)
+    }
+
+    /// the range cut at each Monday, the first and last week may be shorter than 7 days
+    pub fn weeks(&self) -> impl Iterator<Item = DateRange> {
+        self.split(DateRange::week_of)
+    }
+
+    /// the range cut at each first of the month
+    pub fn months(&self) -> impl Iterator<Item = DateRange> {
+        self.split(DateRange::month_of)
+    }
+
+    fn split(&self, period_of: fn(Date) -> DateRange) -> impl Iterator<Item = DateRange> {
+        let mut rest = *self;
+        std::iter::from_fn(move || {
+            if rest.is_empty() {return None}
+            let part = DateRange::new(rest.start, period_of(rest.start).end.min(rest.end));
+            rest.start = part.end + 1;
+            Some(part)
+        })
+    }
+}
+
+impl IntoIterator for DateRange {
+    type Item = Date;
+    type IntoIter = DateIter;
+    fn into_iter(self) -> DateIter {
+        DateIter(self)
     }
 }
 
+/// Every day of a [`DateRange`] in order
+#[derive(Debug, Clone)]
+pub struct DateIter(DateRange);
+
+impl Iterator for DateIter {
+    type Item = Date;
+    fn next(&mut self) -> Option<Date> {
+        if self.0.is_empty() {return None}
+        let date = self.0.start;
+        self.0.start = date + 1;
+        Some(date)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len(), Some(self.0.len()))
+    }
+}
+
+impl DoubleEndedIterator for DateIter {
+    fn next_back(&mut self) -> Option<Date> {
+        if self.0.is_empty() {return None}
+        let date = self.0.end;
+        self.0.end = date - 1;
+        Some(date)
+    }
+}
+
+impl ExactSizeIterator for DateIter {}
+
 impl Serialize for Date {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
@@ -126,37 +540,24 @@ impl<'de> serde::de::Visitor<'de> for DateVisitor {
         let year = if let Some(y) = seq.next_element()? {y} else {return Err(serde::de::Error::missing_field("Year"));};
         let month = if let Some(y) = seq.next_element()? {y} else {return Err(serde::de::Error::missing_field("Month"));};
         let day = if let Some(y) = seq.next_element()? {y} else {return Err(serde::de::Error::missing_field("Day"));};
-        Ok(Date::new_ymd(year, month, day))
+        Date::try_new_ymd(year, month, day).map_err(serde::de::Error::custom)
     }
 }
 
-const fn days_in_month(month: u8) -> u8 {
+/// 0 for a month that doesn't exist
+pub const fn days_in_month(year: i16, month: u8) -> u8 {
     match month {
-        00 => 31,
-        01 => 31,
-        02 => 28,
-        03 => 31,
-        04 => 30,
-        05 => 31,
-        06 => 30,
-        07 => 31,
-        08 => 31,
-        09 => 30,
-        10 => 31,
-        11 => 30,
-        12 => 31,
-        _ => panic!("only months 0-12 are real")
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
     }
 }
 
-const fn is_leap_year(year: i16) -> bool {
-    if year % 100 == 0 {
-        false
-    } else if year % 4 == 0 {
-        true
-    } else {
-        false
-    }
+/// the Gregorian rule, every 4th year except centuries not divisible by 400
+pub const fn is_leap_year(year: i16) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
 }
 
 #[cfg(feature="rocket")]
@@ -169,12 +570,38 @@ impl<'a> rocket::request::FromParam<'a> for Date {
 
 }
 
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if (0..=9999).contains(&self.year()) {
+            write!(f, "{:04}-{:02}-{:02}", self.year(), self.month(), self.day())
+        } else {
+            write!(f, "{:+05}-{:02}-{:02}", self.year(), self.month(), self.day())
+        }
+    }
+}
+
+impl From<Date> for chrono::NaiveDate {
+    fn from(date: Date) -> Self {
+        chrono::NaiveDate::from_ymd_opt(date.year() as i32, date.month() as u32, date.day() as u32)
+            .expect("chrono to cover every year a Date can hold")
+    }
+}
+
+impl TryFrom<chrono::NaiveDate> for Date {
+    type Error = DateError;
+    fn try_from(date: chrono::NaiveDate) -> Result<Self, Self::Error> {
+        use chrono::Datelike;
+        let year = i16::try_from(date.year()).map_err(|_| DateError::YearOutOfRange(date.year()))?;
+        Date::try_new_ymd(year, date.month() as u8, date.day() as u8)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::*;
     #[test]
     fn date_test() {
-        let date = Date::new_ymd(2022, 09, 30);
+        let date = Date::new_ymd(2022, 9, 30);
         assert_eq!(date.year(), 2022);
         assert_eq!(date.day(), 30);
         assert_eq!(date.month(), 9);
@@ -186,7 +613,7 @@ mod test {
 
     #[test]
     fn leap_year() {
-        let date = Date::new_ymd(2020, 02, 29);
+        let date = Date::new_ymd(2020, 2, 29);
         let next = date.next();
         assert_eq!(next.day(), 1);
         assert_eq!(next.month(), 3);
@@ -194,10 +621,108 @@ mod test {
         assert_eq!(prev.day(), 29);
         assert_eq!(prev.month(), 2);
     }
-}
 
-impl std::fmt::Display for Date {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}/{}/{}", self.year(), self.month(), self.day()))
+    #[test]
+    fn gregorian_leap_years() {
+        use crate::date::DateError;
+        use std::str::FromStr;
+        assert!(Date::try_new_ymd(2000, 2, 29).is_ok());
+        assert_eq!(Date::try_new_ymd(1900, 2, 29), Err(DateError::BadDay { year: 1900, month: 2, day: 29 }));
+        assert_eq!(Date::try_new_ymd(2021, 2, 29), Err(DateError::BadDay { year: 2021, month: 2, day: 29 }));
+        assert_eq!(Date::try_new_ymd(2021, 13, 1), Err(DateError::BadMonth(13)));
+        assert_eq!(Date::try_new_ymd(2021, 4, 0), Err(DateError::BadDay { year: 2021, month: 4, day: 0 }));
+        assert_eq!(Date::new_ymd(2000, 2, 28).next(), Date::new_ymd(2000, 2, 29));
+        assert_eq!(Date::new_ymd(1900, 3, 1).prev(), Date::new_ymd(1900, 2, 28));
+        assert_eq!(Date::new_ymd(2023, 1, 1).prev(), Date::new_ymd(2022, 12, 31));
+        assert_eq!(Date::from_str("2021-2-29"), Err(DateError::BadDay { year: 2021, month: 2, day: 29 }));
+        assert_eq!(Date::from_str("2021/x/1"), Err(DateError::Parse("Failed to parse month")));
+        assert_eq!(Date::from_str("2024-2-29"), Ok(Date::new_ymd(2024, 2, 29)));
+        assert!(ron::from_str::<Date>("(2021, 2, 29)").is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        use crate::date::{DateError, DateRange};
+        let date = Date::new_ymd(2022, 12, 30);
+        assert_eq!(date + 3, Date::new_ymd(2023, 1, 2));
+        assert_eq!(date - 365, Date::new_ymd(2021, 12, 30));
+        assert_eq!(Date::new_ymd(2000, 3, 1) - Date::new_ymd(2000, 2, 1), 29);
+        assert_eq!(Date::new_ymd(1970, 1, 1) - 1, Date::new_ymd(1969, 12, 31));
+        assert_eq!(Date::new_ymd(2023, 1, 31).add_months(1), Ok(Date::new_ymd(2023, 2, 28)));
+        assert_eq!(Date::new_ymd(2023, 3, 31).add_months(-13), Ok(Date::new_ymd(2022, 2, 28)));
+        assert_eq!(Date::new_ymd(2024, 2, 29).add_years(1), Ok(Date::new_ymd(2025, 2, 28)));
+        assert_eq!(Date::new_ymd(32767, 12, 1).add_months(1), Err(DateError::YearOutOfRange(32768)));
+        assert_eq!(Date::new_ymd(2022, 1, 1).add_years(i32::MAX), Err(DateError::YearOutOfRange(i32::MAX)));
+        assert_eq!(Date::new_ymd(-32768, 1, 1).checked_add_days(-1), Err(DateError::YearOutOfRange(-32769)));
+        assert!(matches!(Date::new_ymd(32767, 12, 31).checked_add_days(i32::MAX), Err(DateError::YearOutOfRange(_))));
+        assert_eq!(Date::new_ymd(2022, 12, 30).checked_add_days(3), Ok(Date::new_ymd(2023, 1, 2)));
+        assert!(Date::new_ymd(2022, 12, 31) < Date::new_ymd(2023, 1, 1));
+
+        let range = DateRange::new(Date::new_ymd(2023, 1, 30), Date::new_ymd(2023, 3, 2));
+        assert_eq!(range.len(), 32);
+        assert_eq!(range.iter().count(), 32);
+        assert_eq!(range.iter().next_back(), Some(range.end()));
+        assert_eq!(range.into_iter().nth(2), Some(Date::new_ymd(2023, 2, 1)));
+        assert!(range.contains(Date::new_ymd(2023, 2, 28)) && !range.contains(Date::new_ymd(2023, 3, 3)));
+        let months: Vec<_> = range.months().map(|m| (m.start().day(), m.len())).collect();
+        assert_eq!(months, vec![(30, 2), (1, 28), (1, 2)]);
+        // 2023-01-30 is a Monday, 2023-03-02 a Thursday
+        let weeks: Vec<_> = range.weeks().map(|w| w.len()).collect();
+        assert_eq!(weeks, vec![7, 7, 7, 7, 4]);
+        assert_eq!(DateRange::week_of(Date::new_ymd(2023, 3, 2)).start(), Date::new_ymd(2023, 2, 27));
+        assert!(DateRange::new(range.end(), range.start()).is_empty());
+        assert_eq!(DateRange::new(range.end(), range.start()).months().count(), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn weeks_and_seasons() {
+        use crate::date::{DateError, Hemisphere, Season, Seasons, Weekday};
+        assert_eq!(Date::new_ymd(1970, 1, 1).weekday(), Weekday::Thursday);
+        assert_eq!(Date::new_ymd(2023, 10, 18).weekday(), Weekday::Wednesday);
+        assert_eq!(Date::new_ymd(2024, 12, 31).day_of_year(), 366);
+        assert_eq!(Date::new_ymd(2023, 10, 18).iso_week(), (2023, 42));
+        // new year falls in the neighbouring year's week
+        assert_eq!(Date::new_ymd(2021, 1, 3).iso_week(), (2020, 53));
+        assert_eq!(Date::new_ymd(2024, 12, 30).iso_week(), (2025, 1));
+        assert_eq!(Date::from_iso_week(2023, 42, Weekday::Monday), Ok(Date::new_ymd(2023, 10, 16)));
+        assert_eq!(Date::from_iso_week(2025, 1, Weekday::Monday), Ok(Date::new_ymd(2024, 12, 30)));
+        assert_eq!(Date::from_iso_week(2020, 53, Weekday::Sunday), Ok(Date::new_ymd(2021, 1, 3)));
+        assert_eq!(Date::from_iso_week(2021, 53, Weekday::Monday), Err(DateError::BadWeek { year: 2021, week: 53 }));
+
+        let north = Seasons::default();
+        let south = Seasons::meteorological(Hemisphere::Southern);
+        assert_eq!(Date::new_ymd(2023, 1, 15).season(&north), Season::Winter);
+        assert_eq!(Date::new_ymd(2023, 7, 15).season(&north), Season::Summer);
+        assert_eq!(Date::new_ymd(2023, 7, 15).season(&south), Season::Winter);
+        assert_eq!(Date::new_ymd(2023, 12, 1).season(&south), Season::Summer);
+        let winter = north.range(Season::Winter, 2023);
+        assert_eq!((winter.start(), winter.end()), (Date::new_ymd(2023, 12, 1), Date::new_ymd(2024, 2, 29)));
+        let spring = south.range(Season::Spring, 2023);
+        assert_eq!((spring.start(), spring.end()), (Date::new_ymd(2023, 9, 1), Date::new_ymd(2023, 11, 30)));
+        assert_eq!(Seasons::new(Hemisphere::Northern, [(6, 1), (3, 1), (9, 1), (12, 1)]), Err(DateError::SeasonsOutOfOrder));
+        assert!(Seasons::new(Hemisphere::Northern, [(3, 1), (6, 31), (9, 1), (12, 1)]).is_err());
+    }
+
+    #[test]
+    fn iso_format_and_chrono() {
+        use crate::date::DateError;
+        use std::str::FromStr;
+        let date = Date::new_ymd(2022, 9, 1);
+        assert_eq!(date.to_string(), "2022-09-01");
+        assert_eq!(date.to_web_string(), "2022-09-01");
+        assert_eq!(Date::new_ymd(-1, 12, 31).to_string(), "-0001-12-31");
+        assert_eq!(Date::new_ymd(12, 1, 1).to_string(), "0012-01-01");
+        for date in [date, Date::new_ymd(-1, 12, 31), Date::new_ymd(0, 2, 29)] {
+            assert_eq!(Date::from_str(&date.to_string()), Ok(date));
+            let naive = chrono::NaiveDate::from(date);
+            assert_eq!(Date::try_from(naive), Ok(date));
+        }
+        assert_eq!(Date::from_str("2022/9/1"), Ok(date));
+        assert_eq!(Date::from_str("2022-09-01-02"), Err(DateError::Parse("Too many Segs")));
+        assert_eq!(chrono::NaiveDate::from(date), chrono::NaiveDate::from_ymd_opt(2022, 9, 1).unwrap());
+        let far = chrono::NaiveDate::from_ymd_opt(40000, 1, 1).unwrap();
+        assert_eq!(Date::try_from(far), Err(DateError::YearOutOfRange(40000)));
+        let today = Date::today(&chrono::Utc);
+        assert!(today >= Date::new_ymd(2022, 1, 1));
+    }
+}
@@ -0,0 +1,148 @@
+use bevy_reflect::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::*;
+use crate::check::RecordKey;
+
+/// How item and event values are written to storage.
+///
+/// [`ItemData`] and [`EventData`] always carry ron so clients don't care,
+/// with a binary encoding their values are converted through the type registry on the way in and out,
+/// which means raw records of unregistered types can only be stored as ron.
+/// [`Migrations`](crate::migration::Migrations) upgrades also need ron, a binary record can only be read as the type it was written as,
+/// so [`Database::set_migrations`] refuses them on a binary database; aliases for a renamed type still work
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encoding {
+    #[default]
+    Ron,
+    Bincode,
+    MessagePack,
+}
+
+// the meta tree holds settings that have to stay the same for the life of the database,
+// a database without an encoding entry was written before there was a choice, so is ron
+const ENCODING_KEY: &str = "encoding";
+
+impl Encoding {
+    /// encodes `obj` using its registered [`ReflectSerialize`]
+    pub(crate) fn serialize(self, type_registry: &bevy_reflect::TypeRegistry, obj: &dyn Reflect) -> Result<(&'static str, Vec<u8>), DbError> {
+        let registration = match type_registry.get(obj.type_id()) {
+            Some(r) => r,
+            None => return Err(DbError::TypeNotRegistered(obj.type_name().to_string())),
+        };
+        let Some(ser) = registration.data::<ReflectSerialize>() else {return Err(DbError::NoReflectSerialize(registration.type_name()))};
+        let ser = ser.get_serializable(obj.as_reflect());
+        let ser = match &ser {
+            bevy_reflect::serde::Serializable::Owned(s) => &**s,
+            bevy_reflect::serde::Serializable::Borrowed(s) => *s,
+        };
+        let data = match self {
+            Encoding::Ron => ron::to_string(ser)?.into_bytes(),
+            Encoding::Bincode => {
+                use bincode::Options;
+                bincode::options().serialize(ser).map_err(|e| DbError::Encoding(self, e.to_string()))?
+            }
+            Encoding::MessagePack => rmp_serde::to_vec(ser).map_err(|e| DbError::Encoding(self, e.to_string()))?,
+        };
+        Ok((registration.type_name(), data))
+    }
+
+    /// decodes `data` as `type_name` using its registered [`ReflectDeserialize`]
+    pub(crate) fn deserialize(self, type_registry: &bevy_reflect::TypeRegistry, type_name: &str, data: &[u8]) -> Result<Box<dyn Reflect>, DbError> {
+        let Some(registration) = type_registry.get_with_name(type_name) else {return Err(DbError::TypeNotRegistered(type_name.to_string()))};
+        let Some(de) = registration.data::<ReflectDeserialize>() else {return Err(DbError::NoReflectDeSerialize(registration.type_name()))};
+        match self {
+            Encoding::Ron => Ok(de.deserialize(&mut ron::Deserializer::from_bytes(data)?)?),
+            Encoding::Bincode => {
+                let mut de_impl = bincode::Deserializer::from_slice(data, bincode::options());
+                de.deserialize(&mut de_impl).map_err(|e| DbError::Encoding(self, e.to_string()))
+            }
+            Encoding::MessagePack => {
+                let mut de_impl = rmp_serde::Deserializer::from_read_ref(data);
+                de.deserialize(&mut de_impl).map_err(|e| DbError::Encoding(self, e.to_string()))
+            }
+        }
+    }
+
+    /// the stored `data` of a `type_name` as the ron that goes in [`ItemData`] and [`EventData`]
+    pub(crate) fn to_ron(self, type_registry: &bevy_reflect::TypeRegistry, type_name: &str, data: &[u8]) -> Result<String, DbError> {
+        if self == Encoding::Ron {
+            return Ok(String::from_utf8(data.to_vec())?);
+        }
+        let obj = self.deserialize(type_registry, type_name, data)?;
+        Ok(transaction::serialize_obj(type_registry, &*obj)?.1)
+    }
+
+    /// the ron `data` of a `type_name` as it should be stored
+    pub(crate) fn encode_ron(self, type_registry: &bevy_reflect::TypeRegistry, type_name: &str, data: &str) -> Result<Vec<u8>, DbError> {
+        if self == Encoding::Ron {
+            return Ok(data.as_bytes().to_vec());
+        }
+        let obj = Encoding::Ron.deserialize(type_registry, type_name, data.as_bytes())?;
+        Ok(self.serialize(type_registry, &*obj)?.1)
+    }
+}
+
+/// turns a failure to decode the record under `key` into [`DbError::Deserialize`],
+/// a type missing from the registry isn't the record's fault so it passes through
+pub(crate) fn record_error(type_name: &str, key: RecordKey) -> impl FnOnce(DbError) -> DbError + '_ {
+    move |e| match e {
+        DbError::TypeNotRegistered(_) | DbError::NoReflectDeSerialize(_) => e,
+        e => DbError::Deserialize(type_name.to_string(), key, e.to_string()),
+    }
+}
+
+impl Database {
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// picks how values are stored, only allowed while no item or event has been stored
+    /// since existing records would no longer be readable; history and tombstones keep ron either way
+    pub fn set_encoding(&mut self, encoding: Encoding) -> Result<(), DbError> {
+        if encoding == self.encoding {return Ok(())}
+        if !self.items.is_empty() || !self.events.is_empty() {
+            return Err(DbError::EncodingInUse(self.encoding));
+        }
+        if encoding != Encoding::Ron && self.migrations.has_upgrades() {
+            return Err(DbError::UpgradesNeedRon(encoding));
+        }
+        self.meta.insert(ENCODING_KEY, ron::to_string(&encoding)?)?;
+        self.encoding = encoding;
+        Ok(())
+    }
+}
+
+/// the encoding recorded in the meta tree
+pub(crate) fn recorded(meta: &storage::Tree) -> Result<Encoding, DbError> {
+    match meta.get(ENCODING_KEY)? {
+        Some(raw) => Ok(ron::de::from_bytes(&raw)?),
+        None => Ok(Encoding::Ron),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{test_db, test_date};
+    #[test]
+    fn encodings() {
+        use crate::{DbError, Item, encoding::Encoding, plants::{Plant, PlantTypes}};
+        for encoding in [Encoding::Bincode, Encoding::MessagePack] {
+            let mut db = test_db();
+            db.type_registry.register::<Plant>();
+            db.type_registry.register::<PlantTypes>();
+            db.set_encoding(encoding).unwrap();
+            assert_eq!(crate::encoding::recorded(&db.meta).unwrap(), encoding);
+            let plant = Plant::test(0);
+            let id = db.insert(Plant::test(0)).unwrap();
+            assert_ne!(db.items.get(id).unwrap().unwrap(), ron::to_string(&plant).unwrap().into_bytes());
+            assert_eq!(db.get_item_data(id).unwrap().data, ron::to_string(&plant).unwrap());
+            let mut read = db.get_obj(id).unwrap().take::<Plant>().unwrap();
+            read.set_id(plant.id());
+            assert_eq!(read, plant);
+            db.add_event_obj(&plant, test_date()).unwrap();
+            assert_eq!(db.events_on(test_date()).next().unwrap().unwrap().1.data, ron::to_string(&plant).unwrap());
+            assert_eq!(db.event_objs_between(test_date(), test_date()).count(), 1);
+            assert!(matches!(db.set_encoding(Encoding::Ron), Err(DbError::EncodingInUse(e)) if e == encoding));
+        }
+    }
+}
@@ -17,6 +17,21 @@ pub(crate) fn register_types(type_reg: &mut bevy_reflect::TypeRegistry) {
     type_reg.register::<Grade>();
 }
 
+pub struct Greenhouse;
+
+impl crate::module::Module for Greenhouse {
+    fn name(&self) -> &'static str {
+        "greenhouse"
+    }
+    fn register_types(&self, type_reg: &mut bevy_reflect::TypeRegistry) {
+        register_types(type_reg);
+    }
+    #[cfg(feature = "yew")]
+    fn components(&self) -> Vec<(&'static str, ::yew::Html)> {
+        self::yew::components()
+    }
+}
+
 
 #[derive(Debug, Deserialize, Serialize, Reflect, Clone, Copy, FromStr, EnumIter, IntoStaticStr, Default, PartialEq, Eq, Hash)]
 #[reflect_value(Deserialize, Serialize)]
@@ -68,7 +83,7 @@ pub enum Grade {
 }
 
 #[derive(Debug, Deserialize, Serialize, Reflect, Default, Clone, Copy, Hash, PartialEq, Eq)]
-#[reflect(Deserialize, Serialize, Default, ToItem)]
+#[reflect(Deserialize, Serialize, Default, ToItem, Indexed)]
 pub struct Crate {
     #[serde(skip)]
     id: ItemId,
@@ -101,6 +116,10 @@ impl Item for Crate {
     }
 }
 
+impl Indexed for Crate {
+    const INDEXED_FIELDS: &'static [&'static str] = &["crop"];
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServerSideEvent {
     AddedItem(ItemId, Date),
@@ -142,11 +161,31 @@ pub fn date_to_id(date: Date) -> ItemId {
     ItemId(Uuid::new_v3(CONFIG.greenhouse_namespace, &bytes))
 }
 
+/// the days of ISO week `week` of `year`, Monday to Sunday even when they span two months or years
+pub fn week_days(year: i16, week: u8) -> Result<crate::date::DateRange, crate::date::DateError> {
+    Date::from_iso_week(year, week, crate::date::Weekday::Monday).map(crate::date::DateRange::week_of)
+}
+
+/// every crate on the day lists of ISO week `week` of `year`, a day without a list has no crates
+pub fn crates_in_week(db: &Database, year: i16, week: u8) -> Result<Vec<ItemId>, DbError> {
+    let mut crates = Vec::new();
+    for day in week_days(year, week)? {
+        match db.get_item::<Vec<ItemId>>(date_to_id(day)) {
+            Ok(list) => crates.extend(list),
+            Err(DbError::NoTypeName | DbError::NoData) => {},
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(crates)
+}
+
 pub enum GreenHouseMsg {
     Get(ItemId),
     LoadList(Vec<ItemId>),
     LoadItem(Box<dyn Item>),
     SetDate(Date),
+    SetWeek(crate::date::DateRange),
+    AddList(Vec<ItemId>),
     ServerEvent(ServerSideEvent),
     NewCrate,
     EditCrate(ItemId),
@@ -155,4 +194,76 @@ pub enum GreenHouseMsg {
     AddToSum(Crate),
     RemoveFromSum(Crate),
     SetPlot(String),
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn find_by_crop() {
+        let db = Database::with_backend(crate::storage::MemoryBackend::new(), crate::type_registry()).unwrap();
+        let cherry = db.insert(Crate {crop: Crop::CherryTomato, ..Default::default()}).unwrap();
+        let truss = db.insert(Crate {crop: Crop::TrussTomato, grade: Grade::First, ..Default::default()}).unwrap();
+        let found = db.find::<Crate, _>("crop", &Crop::CherryTomato).unwrap();
+        assert_eq!(found.iter().map(|c| c.id).collect::<Vec<_>>(), vec![cherry]);
+        db.insert_item(truss, Crate {crop: Crop::CherryTomato, ..Default::default()}).unwrap();
+        assert_eq!(db.find::<Crate, _>("crop", &Crop::CherryTomato).unwrap().len(), 2);
+        assert!(db.find::<Crate, _>("crop", &Crop::TrussTomato).unwrap().is_empty());
+        db.remove(cherry).unwrap();
+        assert_eq!(db.find::<Crate, _>("crop", &Crop::CherryTomato).unwrap().len(), 1);
+        assert_eq!(db.reindex_fields().unwrap(), 1);
+        assert!(matches!(db.find::<Crate, _>("corp", &Crop::CherryTomato), Err(DbError::NotIndexed(_, field)) if field == "corp"));
+        assert!(matches!(db.find::<Crate, _>("grade", &Grade::First), Err(DbError::NotIndexed(..))));
+    }
+
+    #[test]
+    fn find_by_alias() {
+        let mut db = Database::with_backend(crate::storage::MemoryBackend::new(), crate::type_registry()).unwrap();
+        let mut migrations = crate::migration::Migrations::new();
+        migrations.alias("greenhouse::Crate", std::any::type_name::<Crate>());
+        db.set_migrations(migrations).unwrap();
+        let id = ItemId::from_u128(1);
+        let data = ron::to_string(&Crate {crop: Crop::CherryTomato, ..Default::default()}).unwrap();
+        db.insert_item_data(id, &crate::ItemData { type_name: "greenhouse::Crate".into(), data }).unwrap();
+        assert_eq!(db.find::<Crate, _>("crop", &Crop::CherryTomato).unwrap().len(), 1);
+        db.field_index.clear().unwrap();
+        assert_eq!(db.reindex_fields().unwrap(), 1);
+        assert_eq!(db.find::<Crate, _>("crop", &Crop::CherryTomato).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn week_across_new_year() {
+        let db = Database::with_backend(crate::storage::MemoryBackend::new(), crate::type_registry()).unwrap();
+        // 2020 week 53 runs from Monday December 28th to Sunday January 3rd
+        let week = week_days(2020, 53).unwrap();
+        assert_eq!((week.start(), week.end(), week.len()), (Date::new_ymd(2020, 12, 28), Date::new_ymd(2021, 1, 3), 7));
+        let (monday, sunday, after) = (ItemId::from_u128(1), ItemId::from_u128(2), ItemId::from_u128(3));
+        db.insert_item(date_to_id(Date::new_ymd(2020, 12, 28)), vec![monday]).unwrap();
+        db.insert_item(date_to_id(Date::new_ymd(2021, 1, 3)), vec![sunday]).unwrap();
+        db.insert_item(date_to_id(Date::new_ymd(2021, 1, 4)), vec![after]).unwrap();
+        assert_eq!(crates_in_week(&db, 2020, 53).unwrap(), vec![monday, sunday]);
+        assert_eq!(crates_in_week(&db, 2021, 1).unwrap(), vec![after]);
+        assert!(matches!(crates_in_week(&db, 2021, 53), Err(DbError::DateError(_))));
+    }
+
+    #[test]
+    fn typed_api() {
+        let db = Database::with_backend(crate::storage::MemoryBackend::new(), crate::type_registry()).unwrap();
+        let id = db.put(&Crate {crop: Crop::CherryTomato, ..Default::default()}).unwrap();
+        let stored = db.get::<Crate>(id).unwrap();
+        assert_eq!((stored.id, stored.crop), (id, Crop::CherryTomato));
+        let graded = db.update::<Crate, _>(id, |c| c.grade = Grade::First).unwrap();
+        assert_eq!(db.get::<Crate>(id).unwrap(), graded);
+        assert_eq!(db.put(&graded).unwrap(), id);
+        assert!(matches!(db.get::<crate::plants::Plant>(id), Err(DbError::TypeMissMatch(_))));
+        assert!(matches!(db.update::<crate::plants::Plant, _>(id, |_| {}), Err(DbError::TypeMissMatch(_))));
+        assert!(matches!(db.get_item::<crate::plants::Plant>(id), Err(DbError::TypeMissMatch(_))));
+        let unknown = ItemId::from_u128(1);
+        db.insert_item_data(unknown, &crate::ItemData { type_name: "unknown".into(), data: "()".into() }).unwrap();
+        assert!(matches!(db.get_item::<Crate>(unknown), Err(DbError::TypeMissMatch(stored)) if stored.type_name() == "unknown"));
+        #[derive(serde::Deserialize)]
+        struct Unregistered;
+        assert!(matches!(db.get_item::<Unregistered>(id), Err(DbError::TypeMissMatch(_))));
+        assert_eq!(db.items_of::<Crate>().count(), 1);
+    }
+}
@@ -32,11 +32,19 @@ impl YewObj for Crate {
     }
 }
 use crate::components::CallbackReg;
+
+pub(super) fn components() -> Vec<(&'static str, Html)> {
+    vec![("greenhouse", html! { <GreenHouse /> })]
+}
+
 pub struct GreenHouse {
     data: CallbackReg,
     edit: Option<ItemId>,
     date: Date,
+    /// the ISO week picked, whose days are all loaded, `None` when only `date` is
+    week: Option<crate::date::DateRange>,
     date_node: NodeRef,
+    week_node: NodeRef,
     items: HashSet<ItemId>,
     _sse: gloo_net::eventsource::futures::EventSource,
     sum: HashSet<Crate>,
@@ -46,15 +54,47 @@ pub struct GreenHouse {
 
 impl GreenHouse {
     const MATCH_ITEM_ID: u128 = 1419427672130092986314190298487057552; //01115f2f-f92b-49d7-8b77-842ba7d9ec90
+
+    fn shows(&self, date: Date) -> bool {
+        match self.week {
+            Some(week) => week.contains(date),
+            None => date == self.date,
+        }
+    }
+}
+
+/// fetches the crate list of `date`, an empty one if the server has none
+fn load_list(date: Date, cb: Callback<Vec<ItemId>>) {
+    let id = date_to_id(date);
+    wasm_bindgen_futures::spawn_local(async move {
+        let res = gloo_net::http::Request::get(&format!("/db_item/{}", id.0))
+        .send().await
+        .unwrap();
+        if res.status() == 200 {
+            if let Ok(list) = ron::from_str::<ItemData>(&res.text().await.unwrap()) {
+                if list.type_name == "alloc::vec::Vec<server_lib::items::ItemId>" {
+                    if let Ok(list) = ron::from_str(&list.data) {
+                        cb.emit(list)
+                    } else {
+                        web_sys::console::error_1(&"failed ron for item data".into());
+                    }
+                } else {    
+                    web_sys::console::error_2(&"Wrong type from server: ".into(), &list.type_name.into());
+                }
+            } else {
+                web_sys::console::error_1(&"failed ron to make item".into());
+            }
+        } else {
+            cb.emit(Vec::new());
+        }
+    });
 }
 
 impl Component for GreenHouse {
     type Message = GreenHouseMsg;
     type Properties = ();
     fn create(ctx: &Context<Self>) -> Self {
-        use chrono::Datelike;
-        let today = chrono::Utc::now().date();
-        let now_date =  Date::new_ymd(today.year() as i16, today.month() as u8, today.day() as u8);
+        let now_date = Date::today(&chrono::Local);
         let id = date_to_id(now_date);
         let cb = ctx.link().callback(|item| GreenHouseMsg::LoadList(item));
         wasm_bindgen_futures::spawn_local(async move {
@@ -118,9 +158,7 @@ impl Component for GreenHouse {
             });
         let cb = ctx.link().callback(|plot| GreenHouseMsg::SetPlot(plot));
         wasm_bindgen_futures::spawn_local(async move {
-            let now = chrono::Local::now();
-            let date = now.format("%Y-%m-%d");
-            let res = gloo_net::http::Request::get(&format!("/greenhouse_plot/{}", date))
+            let res = gloo_net::http::Request::get(&format!("/greenhouse_plot/{}", now_date))
             .send().await
             .unwrap();
             if res.status() == 200 {
@@ -142,8 +180,10 @@ impl Component for GreenHouse {
             data: cbr,
             edit: None,
             date: now_date,
+            week: None,
             items: Default::default(),
             date_node: NodeRef::default(),
+            week_node: NodeRef::default(),
             _sse: sse,
             sum: HashSet::new(),
             plot: None,
@@ -153,6 +193,8 @@ impl Component for GreenHouse {
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let date_node = self.date_node.clone();
+        let week_node = self.week_node.clone();
+        let (week_year, week) = self.date.iso_week();
         let loaded_items = self.data.loaded_items.clone();
         let match_item = ItemId::from_u128(GreenHouse::MATCH_ITEM_ID);
         html! {
@@ -161,7 +203,16 @@ impl Component for GreenHouse {
                 <h4>{"Date open is "}<input type="date" ref={self.date_node.clone()}onchange={ctx.link().callback(move |_| {
                     let date = date_node.cast::<HtmlInputElement>().expect("date to be input");
                     GreenHouseMsg::SetDate(Date::from_str(&date.value()).expect("Date to be html date"))
-                })}/></h4>
+                })}/>
+                {" week "}<input type="number" min="1" max="53" ref={self.week_node.clone()} value={week.to_string()} onchange={ctx.link().batch_callback(move |_| {
+                    let week = week_node.cast::<HtmlInputElement>().expect("week to be input");
+                    match week.value().parse().map_err(|_| crate::date::DateError::Parse("Failed to parse week"))
+                        .and_then(|week| super::week_days(week_year, week)) {
+                        Ok(days) => Some(GreenHouseMsg::SetWeek(days)),
+                        Err(e) => {web_sys::console::error_1(&e.to_string().into()); None},
+                    }
+                })}/>
+                {format!(" {} {}", self.date.weekday(), self.date.season(&crate::date::Seasons::default()))}</h4>
                 //<ObjList display={Rc::new(self.items.iter().cloned().collect())}/>
                 {for self.items.iter().map(|item| {let i2 = item.clone(); html!{<div><ObjView id={item.clone()} edit={false}/>
                 <button onclick={ctx.link().callback(move |_| GreenHouseMsg::EditCrate(i2))}>{"edit"}</button></div>}})}
@@ -264,35 +315,13 @@ impl Component for GreenHouse {
             SetDate(new_date) => {
                 //get data for new date
                 self.date = new_date;
-                let id = date_to_id(new_date);
-                let cb = ctx.link().callback(|item| GreenHouseMsg::LoadList(item));
-                wasm_bindgen_futures::spawn_local(async move {
-                    let res = gloo_net::http::Request::get(&format!("/db_item/{}", id.0))
-                    .send().await
-                    .unwrap();
-                    if res.status() == 200 {
-                        if let Ok(list) = ron::from_str::<ItemData>(&res.text().await.unwrap()) {
-                            if list.type_name == "alloc::vec::Vec<server_lib::items::ItemId>" {
-                                if let Ok(list) = ron::from_str(&list.data) {
-                                    cb.emit(list)
-                                } else {
-                                    web_sys::console::error_1(&"failed ron for item data".into());
-                                }
-                            } else {    
-                                web_sys::console::error_2(&"Wrong type from server: ".into(), &list.type_name.into());
-                            }
-                        } else {
-                            web_sys::console::error_1(&"failed ron to make item".into());
-                        }
-                    } else {
-                        cb.emit(Vec::new());
-                    }
-                });
+                self.week = None;
+                load_list(new_date, ctx.link().callback(|item| GreenHouseMsg::LoadList(item)));
                 //get plot for date
                 self.plot = None;
                 let cb = ctx.link().callback(|plot| GreenHouseMsg::SetPlot(plot));
                 wasm_bindgen_futures::spawn_local(async move {
-                    let res = gloo_net::http::Request::get(&format!("/greenhouse_plot/{}", new_date.to_web_string()))
+                    let res = gloo_net::http::Request::get(&format!("/greenhouse_plot/{}", new_date))
                     .send().await
                     .unwrap();
                     if res.status() == 200 {
@@ -300,6 +329,21 @@ impl Component for GreenHouse {
                     }
                 });
                 false},
+            SetWeek(days) => {
+                // every day of the week, which can run into the next month or year
+                self.date = days.start();
+                self.week = Some(days);
+                self.items.clear();
+                self.plot = None;
+                for day in days {
+                    load_list(day, ctx.link().callback(|item| GreenHouseMsg::AddList(item)));
+                }
+                true
+            },
+            AddList(list) => {
+                self.items.extend(list);
+                true
+            },
             NewCrate => {let id = self.data.load(Box::new(Crate::default())); self.edit = Some(id); true},
             SaveCrate => {
                 let id = self.edit.expect("Can only save when have edit");
@@ -352,7 +396,7 @@ impl Component for GreenHouse {
             ServerEvent(e) => {
                 match e {
                     ServerSideEvent::AddedItem(id, date) => {
-                        if date == self.date {
+                        if self.shows(date) {
                             if self.items.insert(id) {
                                 ctx.link().send_message(GreenHouseMsg::Get(id));
                             }
@@ -362,7 +406,7 @@ impl Component for GreenHouse {
                         }
                     },
                     ServerSideEvent::RemovedItem(id, date) => {
-                        if date == self.date {
+                        if self.shows(date) {
                             self.items.remove(&id)
                         } else {
                             false
@@ -389,10 +433,11 @@ impl Component for GreenHouse {
     }
 
     fn rendered(&mut self, _ctx: &Context<Self>, first_render: bool) {
+        // also after picking a week, which moves the date to its Monday
+        let date_val = self.date_node.cast::<HtmlInputElement>().expect("date to be input");
+        let val = self.date.to_string();
+        date_val.set_value(&val);
         if first_render {
-            let date_val = self.date_node.cast::<HtmlInputElement>().expect("date to be input");
-            let val = self.date.to_string().replace("/","-");
-            date_val.set_value(&val);
             web_sys::console::log_1(&val.into());
         }
         if let Some(plot) = &self.plot {
@@ -0,0 +1,114 @@
+use serde::{Serialize, Deserialize};
+use crate::storage::TxTree;
+use crate::*;
+use crate::transaction::{TxResult, abort};
+
+/// One write to an item, kept in the history tree under `(ItemId, revision)`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    /// starts at 1 for the first write to an item
+    pub revision: u64,
+    /// milliseconds since the unix epoch
+    pub timestamp: i64,
+    pub operation: Operation,
+    /// who made the write, when it was made through [`Database::transaction_as`]
+    #[serde(default)]
+    pub actor: Option<String>,
+    /// the type the item was written as, or had when it was removed; empty for revisions recorded before this was kept
+    #[serde(default)]
+    pub type_name: String,
+    /// what the item was before this write, `None` if it did not exist
+    pub previous: Option<ItemData>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operation {
+    Insert,
+    Update,
+    Remove,
+}
+
+// the history tree holds the latest revision number under the bare id
+// and each revision under the id followed by its big endian revision number
+
+fn revision_key(id: ItemId, revision: u64) -> Vec<u8> {
+    let mut key = id.as_bytes().to_vec();
+    key.extend_from_slice(&revision.to_be_bytes());
+    key
+}
+
+fn latest(raw: Option<&[u8]>) -> u64 {
+    raw.and_then(|raw| raw.try_into().ok()).map(u64::from_be_bytes).unwrap_or(0)
+}
+
+pub(crate) fn record(tree: TxTree, id: ItemId, operation: Operation, actor: Option<&str>, type_name: &str, previous: Option<ItemData>) -> TxResult<()> {
+    let revision = latest(tree.get(&id.as_bytes()[..])?.as_deref()) + 1;
+    let entry = Revision {
+        revision,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        operation,
+        actor: actor.map(str::to_string),
+        type_name: type_name.to_string(),
+        previous,
+    };
+    tree.insert(revision_key(id, revision), ron::to_string(&entry).map_err(abort)?.as_str())?;
+    tree.insert(&id.as_bytes()[..], &revision.to_be_bytes()[..])?;
+    Ok(())
+}
+
+impl Database {
+    /// every recorded write to `id`, oldest first
+    pub fn history(&self, id: ItemId) -> Result<Vec<Revision>, DbError> {
+        let mut revisions = Vec::new();
+        for res in self.history.scan_prefix(id) {
+            let (key, data) = res?;
+            if key.len() == std::mem::size_of::<Uuid>() {continue;}
+            revisions.push(ron::de::from_bytes(&data)?);
+        }
+        Ok(revisions)
+    }
+
+    /// the item as it was right after `revision` was written, `None` if that write removed it
+    pub fn get_item_at(&self, id: ItemId, revision: u64) -> Result<Option<ItemData>, DbError> {
+        let latest = latest(self.history.get(id)?.as_deref());
+        if revision == 0 || revision > latest {
+            return Err(DbError::NoRevision(id, revision));
+        }
+        if revision == latest {
+            return match self.get_item_data(id) {
+                Ok(item) => Ok(Some(item)),
+                Err(DbError::NoTypeName) => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+        let Some(next) = self.history.get(revision_key(id, revision + 1))? else {return Err(DbError::NoRevision(id, revision + 1))};
+        Ok(ron::de::from_bytes::<Revision>(&next)?.previous)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::test_db;
+    #[test]
+    fn history() {
+        use crate::{ItemData, ItemId, history::Operation};
+        let db = test_db();
+        let id = ItemId::from_u128(1);
+        let first = ItemData { type_name: "crate".into(), data: "First".into() };
+        let second = ItemData { type_name: "crate".into(), data: "Second".into() };
+        db.insert_item_data(id, &first).unwrap();
+        db.insert_item_data(id, &second).unwrap();
+        db.remove(id).unwrap();
+        let history = db.history(id).unwrap();
+        assert_eq!(history.iter().map(|r| r.operation).collect::<Vec<_>>(), vec![Operation::Insert, Operation::Update, Operation::Remove]);
+        assert_eq!(history[0].previous, None);
+        assert_eq!(history[2].previous, Some(second));
+        assert_eq!(db.get_item_at(id, 1).unwrap(), Some(first));
+        assert_eq!(db.get_item_at(id, 3).unwrap(), None);
+        assert!(db.get_item_at(id, 4).is_err());
+        assert!(db.history(ItemId::from_u128(2)).unwrap().is_empty());
+        assert_eq!(history[0].actor, None);
+        db.transaction_as("grower", |tx| tx.insert_item_data(id, &ItemData { type_name: "crate".into(), data: "Third".into() })).unwrap();
+        assert_eq!(db.history(id).unwrap()[3].actor.as_deref(), Some("grower"));
+    }
+}
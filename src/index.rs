@@ -0,0 +1,246 @@
+use bevy_reflect::{prelude::*, FromType, ReflectRef};
+use crate::*;
+
+/// Implement and add `#[reflect(Indexed)]` to have the [`Database`] keep an index of these fields,
+/// so items can be looked up by value with [`Database::find`]
+pub trait Indexed: Reflect {
+    const INDEXED_FIELDS: &'static [&'static str];
+}
+
+#[derive(Clone)]
+pub struct ReflectIndexed {
+    fields: &'static [&'static str],
+}
+
+impl ReflectIndexed {
+    pub fn fields(&self) -> &'static [&'static str] {
+        self.fields
+    }
+}
+
+impl<T: Indexed> FromType<T> for ReflectIndexed {
+    fn from_type() -> Self {
+        ReflectIndexed { fields: T::INDEXED_FIELDS }
+    }
+}
+
+/// `type_name\0uuid`, so a prefix scan over `type_name\0` finds every item of that type
+pub(crate) fn type_key(type_name: &[u8], id: ItemId) -> Vec<u8> {
+    let mut key = type_prefix(type_name);
+    key.extend_from_slice(id.as_bytes());
+    key
+}
+
+fn type_prefix(type_name: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(type_name.len() + 17);
+    key.extend_from_slice(type_name);
+    key.push(0);
+    key
+}
+
+/// `type_name\0field\0value\0`, value being the field serialized to ron
+fn field_prefix(type_name: &[u8], field: &str, value: &str) -> Vec<u8> {
+    let mut key = type_prefix(type_name);
+    key.extend_from_slice(field.as_bytes());
+    key.push(0);
+    key.extend_from_slice(value.as_bytes());
+    key.push(0);
+    key
+}
+
+/// what an item contributes to the field and dependency indexes
+#[derive(Default)]
+pub(crate) struct ItemIndexes {
+    pub fields: Vec<Vec<u8>>,
+    pub dependencies: Vec<ItemId>,
+}
+
+/// reads the indexed fields and [`Item::dependencies`] of an item;
+/// empty if the type is unknown or has neither `#[reflect(Indexed)]` nor `#[reflect(ToItem)]`;
+/// a type stored under an alias is indexed as the type it resolves to
+pub(crate) fn item_indexes(type_registry: &bevy_reflect::TypeRegistry, migrations: &crate::migration::Migrations, type_name: &[u8], data: &[u8], id: ItemId) -> Result<ItemIndexes, DbError> {
+    let type_name = migrations.resolve(std::str::from_utf8(type_name)?);
+    let Some(registration) = type_registry.get_with_name(type_name) else {return Ok(ItemIndexes::default())};
+    let indexed = registration.data::<ReflectIndexed>();
+    let to_item = registration.data::<crate::items::ReflectToItem>();
+    if indexed.is_none() && to_item.is_none() {return Ok(ItemIndexes::default());}
+    let Some(de) = registration.data::<ReflectDeserialize>() else {return Err(DbError::NoReflectDeSerialize(registration.type_name()))};
+    let obj = de.deserialize(&mut ron::Deserializer::from_bytes(data)?)?;
+    let mut indexes = ItemIndexes::default();
+    if let Some(indexed) = indexed {
+        let ReflectRef::Struct(obj) = obj.reflect_ref() else {return Err(DbError::NotAStruct(registration.type_name()))};
+        for &field in indexed.fields() {
+            let Some(value) = obj.field(field) else {return Err(DbError::NoField(registration.type_name(), field))};
+            let (_, value) = transaction::serialize_obj(type_registry, value)?;
+            let mut key = field_prefix(type_name.as_bytes(), field, &value);
+            key.extend_from_slice(id.as_bytes());
+            indexes.fields.push(key);
+        }
+    }
+    if let Some(item) = to_item.and_then(|t| t.get(&*obj)) {
+        indexes.dependencies = item.as_item().dependencies().unwrap_or_default();
+        indexes.dependencies.retain(|dep| !dep.is_nil() && *dep != id);
+    }
+    Ok(indexes)
+}
+
+// the meta tree records which format the type, field and dependents indexes were built in,
+// a database without the entry was written before they existed
+const INDEX_FORMAT_KEY: &str = "index_format";
+const INDEX_FORMAT: u32 = 1;
+
+impl Database {
+    /// rebuilds the indexes when they are older than this build, so a database from before them
+    /// doesn't answer [`Database::find`] or [`Database::dependents_of`] with nothing
+    pub(crate) fn ensure_indexes(&self) -> Result<(), DbError> {
+        let format: u32 = match self.meta.get(INDEX_FORMAT_KEY)? {
+            Some(raw) => ron::de::from_bytes(&raw)?,
+            None => 0,
+        };
+        if format >= INDEX_FORMAT {return Ok(())}
+        let rebuilt = if self.items.is_empty() {Ok(())} else {self.reindex()};
+        match rebuilt.and_then(|()| self.meta.insert(INDEX_FORMAT_KEY, ron::to_string(&INDEX_FORMAT)?)) {
+            // nothing to index, a read only database can do without the entry
+            Err(DbError::ReadOnly) if self.items.is_empty() => Ok(()),
+            Err(DbError::ReadOnly) => Err(DbError::StaleIndexes),
+            res => res.map(|_| ()),
+        }
+    }
+
+    /// rebuilds the type, field and dependents indexes from the stored items
+    pub fn reindex(&self) -> Result<(), DbError> {
+        self.reindex_types()?;
+        self.reindex_fields()?;
+        self.reindex_dependents()?;
+        Ok(())
+    }
+
+    /// the id of every item stored with `type_name`
+    pub fn items_of_type(&self, type_name: &str) -> impl Iterator<Item = Result<ItemId, DbError>> + '_ {
+        let prefix = type_prefix(type_name.as_bytes());
+        let len = prefix.len();
+        self.type_index.scan_prefix(prefix).keys().map(move |key| ItemId::try_from(&key?[len..]))
+    }
+
+    /// every `T` in the database, deserialized through the type registry;
+    /// only [`DbError::TypeNotRegistered`] if `T` isn't registered
+    pub fn items_of<T: Item>(&self) -> impl Iterator<Item = Result<T, DbError>> + '_ {
+        let (type_name, unregistered) = match self.registered_name::<T>() {
+            Ok(type_name) => (Some(type_name), None),
+            Err(e) => (None, Some(Err(e))),
+        };
+        unregistered.into_iter().chain(type_name.into_iter().flat_map(move |type_name| {
+            self.items_of_type(type_name).map(move |id| self.get(id?))
+        }))
+    }
+
+    /// every `T` whose indexed `field` equals `value`, [`DbError::NotIndexed`] if `field` isn't in [`Indexed::INDEXED_FIELDS`]
+    pub fn find<T: Item, V: Reflect>(&self, field: &str, value: &V) -> Result<Vec<T>, DbError> {
+        let Some(registration) = self.type_registry.get(std::any::TypeId::of::<T>()) else {
+            return Err(DbError::TypeNotRegistered(std::any::type_name::<T>().to_string()));
+        };
+        if !registration.data::<ReflectIndexed>().is_some_and(|indexed| indexed.fields().contains(&field)) {
+            return Err(DbError::NotIndexed(registration.type_name(), field.to_string()));
+        }
+        let (_, value) = transaction::serialize_obj(&self.type_registry, value)?;
+        let prefix = field_prefix(registration.type_name().as_bytes(), field, &value);
+        let mut found = Vec::new();
+        for key in self.field_index.scan_prefix(&prefix).keys() {
+            let id = ItemId::try_from(&key?[prefix.len()..])?;
+            found.push(self.get(id)?);
+        }
+        Ok(found)
+    }
+
+    /// rebuilds the type index from the type tree, for databases written before the index existed;
+    /// returns how many items were indexed
+    pub fn reindex_types(&self) -> Result<usize, DbError> {
+        self.type_index.clear()?;
+        let mut count = 0;
+        for res in self.type_tree.iter() {
+            let (key, type_name) = res?;
+            let Ok(id) = ItemId::try_from(key.as_ref()) else {continue};
+            self.type_index.insert(type_key(&type_name, id), &b""[..])?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// rebuilds the field index from the stored items, for databases written before a type was `#[reflect(Indexed)]`;
+    /// returns how many index entries were written
+    pub fn reindex_fields(&self) -> Result<usize, DbError> {
+        self.field_index.clear()?;
+        let mut count = 0;
+        for key in self.items.iter().keys() {
+            let id = ItemId::try_from(key?.as_ref())?;
+            // an item that can't be read has nothing to index, Database::check reports it
+            let Ok(item) = self.get_item_data(id) else {continue};
+            let Ok(indexes) = item_indexes(&self.type_registry, &self.migrations, item.type_name.as_bytes(), item.data.as_bytes(), id) else {continue};
+            for key in indexes.fields {
+                self.field_index.insert(key, &b""[..])?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use crate::test::test_db;
+    use crate::plants::{Plant, PlantTypes};
+    #[test]
+    fn items_of_type() {
+        use crate::{ItemData, plants::{Plant, PlantTypes}};
+        let mut db = test_db();
+        db.type_registry.register::<Plant>();
+        db.type_registry.register::<PlantTypes>();
+        let plant = db.insert(Plant::test(0)).unwrap();
+        let other = db.add_item(&ItemData { type_name: "other".into(), data: "()".into() }).unwrap();
+        let plants = db.items_of::<Plant>().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(plants.len(), 1);
+        assert_eq!(crate::Item::id(&plants[0]), plant);
+        assert_eq!(db.items_of_type("other").collect::<Result<Vec<_>, _>>().unwrap(), vec![other]);
+        db.insert_item_data(other, &ItemData { type_name: "changed".into(), data: "()".into() }).unwrap();
+        assert_eq!(db.items_of_type("other").count(), 0);
+        assert_eq!(db.items_of_type("changed").count(), 1);
+        db.remove(plant).unwrap();
+        assert_eq!(db.items_of::<Plant>().count(), 0);
+        db.type_index.clear().unwrap();
+        assert_eq!(db.reindex_types().unwrap(), 1);
+        assert_eq!(db.items_of_type("changed").next().unwrap().unwrap(), other);
+    }
+
+    #[test]
+    fn stale_indexes() {
+        use std::sync::Arc;
+        use crate::{ItemData, ItemId, DbError, integrity::{Integrity, RemovePolicy}, storage::{MemoryBackend, ReadOnlyBackend}};
+        let backend = Arc::new(MemoryBackend::new());
+        let open = || {
+            let mut registry = crate::type_registry();
+            registry.register::<Plant>();
+            registry.register::<PlantTypes>();
+            Database::with_backend(backend.clone(), registry)
+        };
+        let db = open().unwrap();
+        let tray = ItemId::from_u128(1001);
+        db.insert_item_data(tray, &ItemData { type_name: "tray".into(), data: "()".into() }).unwrap();
+        let plant = db.put(&Plant::test(0)).unwrap();
+        // as a database from before the indexes would look
+        for tree in [&db.type_index, &db.field_index, &db.dependents] {
+            tree.clear().unwrap();
+        }
+        db.meta.remove("index_format").unwrap();
+        drop(db);
+        assert!(matches!(Database::with_backend(ReadOnlyBackend(backend.clone()), crate::type_registry()), Err(DbError::StaleIndexes)));
+        let mut db = open().unwrap();
+        assert_eq!(db.dependents_of(tray).unwrap(), vec![plant]);
+        assert_eq!(db.items_of::<Plant>().count(), 1);
+        db.set_integrity(Integrity { check_dependencies: false, on_remove: RemovePolicy::Restrict });
+        assert!(matches!(db.remove(tray), Err(DbError::HasDependents(..))));
+        assert!(db.meta.get("index_format").unwrap().is_some());
+        drop(db);
+        assert!(Database::with_backend(ReadOnlyBackend(backend), crate::type_registry()).is_ok());
+    }
+}
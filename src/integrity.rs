@@ -0,0 +1,114 @@
+use crate::storage::TxTree;
+use crate::transaction::TxResult;
+use crate::*;
+
+/// How strictly the [`Database`] holds items to their [`Item::dependencies`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Integrity {
+    /// refuse to insert an item if one of its dependencies is not in the database
+    pub check_dependencies: bool,
+    pub on_remove: RemovePolicy,
+}
+
+/// What [`Database::remove`] does with an item other items still depend on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RemovePolicy {
+    /// remove it anyway and leave the dependents pointing at nothing
+    #[default]
+    Allow,
+    /// fail with [`DbError::HasDependents`]
+    Restrict,
+    /// remove everything that depends on it as well
+    Cascade,
+}
+
+// the dependents tree maps an item to the concatenated ids of every item that depends on it,
+// one value per item so it can be read and updated inside a transaction
+
+pub(crate) fn parse_dependents(raw: Option<&[u8]>) -> Vec<ItemId> {
+    raw.unwrap_or_default().chunks_exact(std::mem::size_of::<Uuid>())
+        .filter_map(|id| ItemId::try_from(id).ok())
+        .collect()
+}
+
+pub(crate) fn add_dependent(tree: TxTree, on: ItemId, dependent: ItemId) -> TxResult<()> {
+    let mut dependents = parse_dependents(tree.get(&on.as_bytes()[..])?.as_deref());
+    if !dependents.contains(&dependent) {
+        dependents.push(dependent);
+        tree.insert(&on.as_bytes()[..], join(&dependents))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn remove_dependent(tree: TxTree, on: ItemId, dependent: ItemId) -> TxResult<()> {
+    let mut dependents = parse_dependents(tree.get(&on.as_bytes()[..])?.as_deref());
+    dependents.retain(|id| *id != dependent);
+    if dependents.is_empty() {
+        tree.remove(&on.as_bytes()[..])?;
+    } else {
+        tree.insert(&on.as_bytes()[..], join(&dependents))?;
+    }
+    Ok(())
+}
+
+fn join(ids: &[ItemId]) -> Vec<u8> {
+    ids.iter().flat_map(|id| *id.as_bytes()).collect()
+}
+
+impl Database {
+    pub fn set_integrity(&mut self, integrity: Integrity) {
+        self.integrity = integrity;
+    }
+
+    /// every item whose [`Item::dependencies`] contains `id`, so you can ask what points at a SeedTray
+    pub fn dependents_of(&self, id: ItemId) -> Result<Vec<ItemId>, DbError> {
+        Ok(parse_dependents(self.dependents.get(id)?.as_deref()))
+    }
+
+    /// rebuilds the dependents tree from the stored items, for databases written before it existed;
+    /// returns how many dependencies were recorded
+    pub fn reindex_dependents(&self) -> Result<usize, DbError> {
+        self.dependents.clear()?;
+        let mut dependents = std::collections::HashMap::<ItemId, Vec<ItemId>>::new();
+        let mut count = 0;
+        for key in self.items.iter().keys() {
+            let id = ItemId::try_from(key?.as_ref())?;
+            // an item that can't be read has no dependencies we know of, Database::check reports it
+            let Ok(item) = self.get_item_data(id) else {continue};
+            let Ok(indexes) = index::item_indexes(&self.type_registry, &self.migrations, item.type_name.as_bytes(), item.data.as_bytes(), id) else {continue};
+            for dep in indexes.dependencies {
+                dependents.entry(dep).or_default().push(id);
+                count += 1;
+            }
+        }
+        for (dep, ids) in dependents {
+            self.dependents.insert(dep, join(&ids))?;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::test_db;
+    #[test]
+    fn dependencies() {
+        use crate::{ItemData, ItemId, DbError, integrity::{Integrity, RemovePolicy}, plants::{Plant, PlantTypes}};
+        let mut db = test_db();
+        db.type_registry.register::<Plant>();
+        db.type_registry.register::<PlantTypes>();
+        db.set_integrity(Integrity { check_dependencies: true, on_remove: RemovePolicy::Restrict });
+        let tray = ItemId::from_u128(1001);
+        assert!(matches!(db.insert(Plant::test(0)), Err(DbError::MissingDependency(_, id)) if id == tray));
+        db.insert_item_data(tray, &ItemData { type_name: "tray".into(), data: "()".into() }).unwrap();
+        let plant = db.insert(Plant::test(0)).unwrap();
+        assert_eq!(db.dependents_of(tray).unwrap(), vec![plant]);
+        assert!(matches!(db.remove(tray), Err(DbError::HasDependents(id, _)) if id == tray));
+        db.dependents.clear().unwrap();
+        assert_eq!(db.reindex_dependents().unwrap(), 1);
+        db.set_integrity(Integrity { check_dependencies: true, on_remove: RemovePolicy::Cascade });
+        db.remove(tray).unwrap();
+        assert!(db.get_item_data(plant).is_err());
+        assert!(db.dependents_of(tray).unwrap().is_empty());
+    }
+}
@@ -5,7 +5,7 @@ use crate::*;
 #[cfg(feature="yew")]
 pub use self::yew_impl::{YewObj, ObjList, ObjView};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ItemData {
     pub type_name: String,
     pub data: String,
@@ -16,8 +16,19 @@ pub struct EventData {
     pub type_name: String,
     pub data: String,
     pub date: Date,
+    /// when in the day the event happened, its date has to be `date`
+    #[serde(default)]
+    pub time: Option<Timestamp>,
 }
 
+/// the items to fetch in one round trip, as a request body
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemIds(pub Vec<ItemId>);
+
+/// items to save in one round trip with [`Database::insert_many`], as a request body
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemBatch(pub Vec<(ItemId, ItemData)>);
+
 #[cfg(feature = "rocket")]
 mod rocket {
     use std::str::FromStr;
@@ -63,6 +74,37 @@ mod rocket {
         }
     }
 
+    async fn ron_body<'r, T: serde::de::DeserializeOwned>(data: Data<'r>) -> data::Outcome<'r, T, DbError> {
+        use rocket::data::ToByteUnit;
+        let datastream = data.open(2.megabytes());
+        let data_string = match datastream.into_string().await {
+            Ok(str) => str,
+            Err(e) => return data::Outcome::Failure((rocket::http::Status::InternalServerError, e.into())),
+        };
+        match ron::from_str(&data_string) {
+            Ok(body) => data::Outcome::Success(body),
+            Err(e) => data::Outcome::Failure((rocket::http::Status::InternalServerError, e.into())),
+        }
+    }
+
+    #[rocket::async_trait]
+    impl<'r> rocket::data::FromData<'r> for super::ItemIds {
+        type Error = DbError;
+
+        async fn from_data(_: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+            ron_body(data).await
+        }
+    }
+
+    #[rocket::async_trait]
+    impl<'r> rocket::data::FromData<'r> for super::ItemBatch {
+        type Error = DbError;
+
+        async fn from_data(_: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+            ron_body(data).await
+        }
+    }
+
     impl<'a> rocket::request::FromParam<'a> for super::EventId {
         type Error = <u64 as std::str::FromStr>::Err;
 
@@ -80,9 +122,19 @@ mod rocket {
     }
 }
 
+// Events with a time set this bit, one of the 7 the date leaves unused, so they sort after the events
+// of the day without one; below it come the second of the day and a counter shared with the untimed events
+const TIMED: u64 = 1 << 38;
+const TIMED_VAL_BITS: u32 = 21;
+const DAY_BITS: u64 = (1 << 39) - 1;
+const TIMED_SECONDS: u64 = (TIMED - 1) >> TIMED_VAL_BITS;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct EventId([u8; 8]);
 impl EventId {
+    /// the highest [`EventId::val`] a key with a time can hold
+    pub const MAX_TIMED_VAL: u64 = (1 << TIMED_VAL_BITS) - 1;
+
     pub fn date_key(date: Date) -> EventId {
         let data: [u8; 8] = ((date.0 as u64) << 32).to_be_bytes();
         EventId(data)
@@ -94,24 +146,68 @@ impl EventId {
         let data: [u8;4] = [self.0[0], self.0[1], self.0[2], self.0[3] & 0x80];
         Date(u32::from_be_bytes(data))
     }
+    /// the first key of the events at `time`, add a counter with [`EventId::with_id`]
+    pub fn time_key(time: Timestamp) -> EventId {
+        // a Timestamp is always within its day, the mask only keeps one that isn't from spilling into the date
+        let seconds = time.seconds() as u64 & TIMED_SECONDS;
+        let key = EventId::date_key(time.date()).as_u64() | TIMED | seconds << TIMED_VAL_BITS;
+        EventId(key.to_be_bytes())
+    }
+    pub fn is_timed(&self) -> bool {
+        self.as_u64() & TIMED != 0
+    }
+    /// the time a key from [`EventId::time_key`] is at
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        if !self.is_timed() {return None}
+        let seconds = (self.as_u64() & DAY_BITS & !TIMED) >> TIMED_VAL_BITS;
+        Timestamp::from_seconds(self.date_from_key(), seconds as u32).ok()
+    }
+    fn as_u64(&self) -> u64 {
+        u64::from_be_bytes(self.0)
+    }
     pub fn set_val(&mut self, num: u64) {
+        if self.is_timed() {
+            self.0 = (self.as_u64() & !EventId::MAX_TIMED_VAL | num & EventId::MAX_TIMED_VAL).to_be_bytes();
+            return;
+        }
         let num = num.to_be_bytes();
         self.0[4] = (self.0[4] & 0x80) | (num[4] & 0x7F);
-        for i in 5..8 {
-            self.0[i] = num[i];
+        self.0[5..8].copy_from_slice(&num[5..8]);
+    }
+    /// the number of the event within its day, what [`EventId::set_val`] set
+    pub fn val(&self) -> u64 {
+        if self.is_timed() {
+            return self.as_u64() & EventId::MAX_TIMED_VAL;
         }
+        u64::from_be_bytes([0, 0, 0, 0, self.0[4] & 0x7F, self.0[5], self.0[6], self.0[7]])
     }
     pub fn set_date(&mut self, date: Date) {
         let date = date.0.to_be_bytes();
-        for i in 0..3 {
-            self.0[i] = date[i];
-        }
+        self.0[..3].copy_from_slice(&date[..3]);
         self.0[3] = (date[3] & 0x80) | (self.0[3] & 0x7F);
     }
     pub fn with_id(mut self, id: u64) -> EventId {
         self.set_val(id);
         self
     }
+    /// the range of keys that covers every event from `from` to `to` (both inclusive), with a time or without
+    pub fn date_range(from: Date, to: Date) -> std::ops::RangeInclusive<EventId> {
+        EventId::date_key(from)..=EventId((EventId::date_key(to).as_u64() | DAY_BITS).to_be_bytes())
+    }
+    /// the range of keys that covers every event with a time from `from` to `to` (both inclusive)
+    pub fn time_range(from: Timestamp, to: Timestamp) -> std::ops::RangeInclusive<EventId> {
+        EventId::time_key(from)..=EventId::time_key(to).with_id(EventId::MAX_TIMED_VAL)
+    }
+}
+
+impl TryFrom<&[u8]> for EventId {
+    type Error = DbError;
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        match value.try_into() {
+            Ok(data) => Ok(EventId(data)),
+            Err(_) => Err(DbError::BadEventKey(value.len())),
+        }
+    }
 }
 
 impl AsRef<[u8]> for EventId {
@@ -120,9 +216,9 @@ impl AsRef<[u8]> for EventId {
     }
 }
 
-impl ToString for EventId {
-    fn to_string(&self) -> String {
-        u64::from_be_bytes(self.0).to_string()
+impl std::fmt::Display for EventId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", u64::from_be_bytes(self.0))
     }
 }
 
@@ -181,9 +277,9 @@ impl<'de> Deserialize<'de> for ItemId {
     }
 }
 
-impl ToString for ItemId {
-    fn to_string(&self) -> String {
-        self.0.to_string()
+impl std::fmt::Display for ItemId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
@@ -194,6 +290,16 @@ impl std::str::FromStr for ItemId {
     }
 }
 
+impl TryFrom<&[u8]> for ItemId {
+    type Error = DbError;
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        match Uuid::from_slice(value) {
+            Ok(uuid) => Ok(ItemId(uuid)),
+            Err(_) => Err(DbError::BadItemKey(value.len())),
+        }
+    }
+}
+
 impl AsRef<Uuid> for ItemId {
     fn as_ref(&self) -> &Uuid {
         &self.0
@@ -202,7 +308,7 @@ impl AsRef<Uuid> for ItemId {
 
 impl AsRef<[u8]> for ItemId {
     fn as_ref(&self) -> &[u8] {
-        &self.0.as_ref()
+        self.0.as_ref()
     }
 }
 
@@ -583,6 +689,7 @@ pub mod yew_impl {
 
 #[cfg(test)]
 mod test {
+    use crate::test::test_db;
     #[test]
     fn reflect() {
         use crate::*;
@@ -613,6 +720,25 @@ mod test {
     assert!(obj0.is::<Plant>());
 }
 
+    #[test]
+    fn batches() {
+        use crate::{ItemData, ItemId, DbError, items::{ItemBatch, ItemIds}};
+        let mut db = test_db();
+        let item = |data: &str| ItemData { type_name: "Pot".into(), data: data.into() };
+        let batch = ItemBatch(vec![(ItemId::from_u128(1), item("(1)")), (ItemId::from_u128(2), item("(2)"))]);
+        let batch: ItemBatch = ron::from_str(&ron::to_string(&batch).unwrap()).unwrap();
+        db.insert_many(&batch.0).unwrap();
+        let ids: ItemIds = ron::from_str(&ron::to_string(&ItemIds(vec![ItemId::from_u128(2), ItemId::from_u128(3), ItemId::from_u128(1)])).unwrap()).unwrap();
+        let found = db.get_many(&ids.0);
+        assert_eq!(found[0].as_ref().unwrap(), &item("(2)"));
+        assert!(matches!(found[1], Err(DbError::NoTypeName)));
+        assert_eq!(found[2].as_ref().unwrap(), &item("(1)"));
+        // one bad item keeps the whole batch out
+        db.type_registry.register::<crate::plants::Plant>();
+        let garbled = ItemData { type_name: std::any::type_name::<crate::plants::Plant>().into(), data: "not a plant".into() };
+        assert!(db.insert_many(&[(ItemId::from_u128(4), item("(4)")), (ItemId::from_u128(5), garbled)]).is_err());
+        assert!(db.get_item_data(ItemId::from_u128(4)).is_err());
+    }
 }
 #[derive(Debug, Reflect)]
 pub struct TestString {
@@ -5,11 +5,14 @@ use uuid::Uuid;
 pub use prelude::*;
 mod prelude {
     pub use crate::date::Date;
+    pub use crate::timestamp::Timestamp;
     pub use crate::items::ItemData;
     pub use crate::items::EventData;
     pub use crate::items::EventId;
     pub use crate::items::ItemId;
     pub use crate::items::Item;
+    pub use crate::transaction::DbTransaction;
+    pub use crate::index::{Indexed, ReflectIndexed};
     #[cfg(feature = "yew")]
     pub use crate::items::{YewObj, ObjList, yew_impl::ObjMsg, /*yew_impl::LoadedItems,*/ ObjView};
     //#[cfg(feature = "yew")]
@@ -17,9 +20,24 @@ mod prelude {
 }
 pub mod items;
 pub mod date;
+pub mod timestamp;
 pub mod worms;
 pub mod plants;
 pub mod greenhouse;
+pub mod transaction;
+pub mod index;
+pub mod integrity;
+pub mod history;
+pub mod tombstone;
+pub mod versions;
+pub mod migration;
+pub mod storage;
+pub mod encoding;
+pub mod archive;
+pub mod check;
+pub mod subscribe;
+pub mod config;
+pub mod module;
 #[cfg(feature = "yew")]
 pub mod components;
 
@@ -51,210 +69,366 @@ pub struct Metadata {
     pub content_id: Uuid,
 }
 
+/// every tree of a [`Database`], in the order [`Database::transaction`] hands them to [`DbTransaction`]
+const TREES: [&str; 11] = [storage::DEFAULT_TREE, "items", "events", "types", "type_index", "field_index", "dependents", "history", "tombstones", "versions", "schemas"];
+
 pub struct Database {
     type_registry: bevy_reflect::TypeRegistry,
-    db: sled::Db,
-    events: sled::Tree,
-    type_tree: sled::Tree,
+    backend: Box<dyn storage::StorageBackend>,
+    db: storage::Tree,
+    items: storage::Tree,
+    events: storage::Tree,
+    type_tree: storage::Tree,
+    type_index: storage::Tree,
+    field_index: storage::Tree,
+    dependents: storage::Tree,
+    history: storage::Tree,
+    tombstones: storage::Tree,
+    versions: storage::Tree,
+    schemas: storage::Tree,
+    meta: storage::Tree,
+    integrity: integrity::Integrity,
+    migrations: migration::Migrations,
+    encoding: encoding::Encoding,
 }
 
 impl Database {
-    fn get_next_key(&self, date: Date) -> Result<EventId, DbError> {
-        use bincode::Options;
-        let key = EventId::date_key(date);
-        let options = bincode::options().with_big_endian();
-        let next = self.db.update_and_fetch(key, |v| {
-            let mut next = match v {
-                Some(raw) => {
-                    options.deserialize::<u64>(raw).unwrap_or(0)
-                },
-                None => 0,
+    /// runs `f` as one atomic write across every tree;
+    /// `f` may be called more than once if another writer conflicts with it
+    pub fn transaction<F, A>(&self, f: F) -> Result<A, DbError>
+    where F: Fn(&DbTransaction) -> transaction::TxResult<A> {
+        self.transaction_with(self.integrity, None, f)
+    }
+    /// [`Database::transaction`] recording `actor` as the one who made every write in the history
+    pub fn transaction_as<F, A>(&self, actor: &str, f: F) -> Result<A, DbError>
+    where F: Fn(&DbTransaction) -> transaction::TxResult<A> {
+        self.transaction_with(self.integrity, Some(actor), f)
+    }
+    /// [`Database::transaction`] holding the writes to `integrity` instead of the database's own
+    fn transaction_with<F, A>(&self, integrity: integrity::Integrity, actor: Option<&str>, f: F) -> Result<A, DbError>
+    where F: Fn(&DbTransaction) -> transaction::TxResult<A> {
+        let result = std::cell::RefCell::new(None);
+        self.backend.transaction(&TREES, &|trees| {
+            let tree = |i: usize| storage::TxTree::new(trees[i]);
+            let tx = DbTransaction {
+                type_registry: &self.type_registry,
+                db: tree(0),
+                items: tree(1),
+                events: tree(2),
+                type_tree: tree(3),
+                type_index: tree(4),
+                field_index: tree(5),
+                dependents: tree(6),
+                history: tree(7),
+                tombstones: tree(8),
+                versions: tree(9),
+                schemas: tree(10),
+                integrity,
+                actor,
+                migrations: &self.migrations,
+                encoding: self.encoding,
             };
-            next += 1;
-            Some(options.serialize(&next).unwrap())
-        })?.expect("will return 1 if it did not find a pre exising num");
-        let id: u64 = options.deserialize(&next).unwrap();
-        Ok(key.with_id(id))
+            *result.borrow_mut() = Some(f(&tx)?);
+            Ok(())
+        })?;
+        result.into_inner().ok_or(DbError::TransactionNotRun)
+    }
+    #[cfg(test)]
+    fn get_next_key(&self, date: Date) -> Result<EventId, DbError> {
+        self.transaction(|tx| tx.next_event_id(date))
     }
     #[inline(always)]
     pub fn add_event(&self, event: &EventData) -> Result<EventId, DbError> {
-        let key = self.get_next_key(event.date)?;
-        self.type_tree.insert(key, event.type_name.as_str())?;
-        self.events.insert(key, event.data.as_str())?;
-        Ok(key)
+        self.transaction(|tx| tx.add_event(event))
     }
-    pub fn get_event_obj(&self, key: EventId) -> anyhow::Result<Box<dyn Reflect>> {
-        let type_name = if let Some(name) = self.type_tree.get(key)? {
-            String::from_utf8(name.to_vec())?
-        } else {
-            return Err(DbError::NoTypeName.into());
-        };
-        let registration = match self.type_registry.get_with_name(&type_name) {
-            Some(r) => r,
-            None => {return Err(DbError::TypeNotRegistered(type_name).into())}
-        };
-        let ser = match registration.data::<ReflectDeserialize>() {
-            Some(s) => s,
-            None => return Err(DbError::NoReflectDeSerialize(registration.type_name()).into()),
-        };
-        let data = if let Some(data) = self.events.get(key)? {
-            println!("{}", String::from_utf8_lossy(&data));
-            data
-        } else {
-            return Err(DbError::NoData.into());
-        };
-        let mut de = ron::Deserializer::from_bytes(&data)?;
-        //let mut de = bincode::Deserializer::from_slice(&data, bincode::options());
-        Ok(ser.deserialize(&mut de)?)
-    }
-    pub fn add_event_obj(&self, event: &dyn Reflect, date: Date) -> anyhow::Result<EventId> {
-        let key = self.get_next_key(date)?;
-        let registration = match self.type_registry.get(event.type_id()) {
-            Some(r) => r,
-            None => {return Err(DbError::TypeNotRegistered(event.type_name().to_string()).into())}
-        };
-        let ser = match registration.data::<ReflectSerialize>() {
-            Some(s) => s,
-            None => return Err(DbError::NoReflectSerialize(registration.type_name()).into()),
-        };
-        let ser = match ser.get_serializable(event.as_reflect()) {
-            bevy_reflect::serde::Serializable::Owned(s) => ron::to_string(&s),
-            bevy_reflect::serde::Serializable::Borrowed(s) => ron::to_string(s),
-        }?;
-        self.type_tree.insert(key, registration.type_name())?;
-        self.events.insert(key, ser.as_str())?;
-        Ok(key)
-    }
-    pub fn insert<T: Serialize + Reflect>(&self, item: T) -> anyhow::Result<ItemId> {
-        let uuid = Uuid::new_v4();
-        let registration = match self.type_registry.get(item.type_id()) {
-            Some(r) => r,
-            None => {return Err(DbError::TypeNotRegistered(item.type_name().to_string()).into())}
-        };
-        let ser = match registration.data::<ReflectSerialize>() {
-            Some(s) => s,
-            None => return Err(DbError::NoReflectSerialize(registration.type_name()).into()),
-        };
-        let ser = match ser.get_serializable(item.as_reflect()) {
-            bevy_reflect::serde::Serializable::Owned(s) => ron::to_string(&s),
-            bevy_reflect::serde::Serializable::Borrowed(s) => ron::to_string(s),
-        }?;
-        self.type_tree.insert(uuid, registration.type_name())?;
-        self.events.insert(uuid, ser.as_str())?;
-        Ok(ItemId(uuid))
-    }
-    pub fn get_obj(&self, key: ItemId) -> anyhow::Result<Box<dyn Reflect>> {
+    pub fn get_event_obj(&self, key: EventId) -> Result<Box<dyn Reflect>, DbError> {
         let type_name = if let Some(name) = self.type_tree.get(key)? {
             String::from_utf8(name.to_vec())?
         } else {
-            return Err(DbError::NoTypeName.into());
-        };
-        let registration = match self.type_registry.get_with_name(type_name.as_str()) {
-            Some(r) => r,
-            None => {return Err(DbError::TypeNotRegistered(type_name.into()).into())}
-        };
-        let ser = match registration.data::<ReflectDeserialize>() {
-            Some(s) => s,
-            None => return Err(DbError::NoReflectDeSerialize(registration.type_name()).into()),
-        };
-        let data = if let Some(data) = self.events.get(key)? {
-            data
-        } else {
-            return Err(DbError::NoData.into());
+            return Err(DbError::NoTypeName);
         };
-        let mut de = ron::Deserializer::from_bytes(&data)?;
-        //let mut de = bincode::Deserializer::from_slice(&data, bincode::options());
-        Ok(ser.deserialize(&mut de).unwrap())
+        let Some(data) = self.events.get(key)? else {return Err(DbError::NoData)};
+        self.deserialize_obj(key.into(), &type_name, &data)
+    }
+    /// decodes the `data` stored under `key` in the items or events tree
+    fn deserialize_obj(&self, key: check::RecordKey, type_name: &str, data: &[u8]) -> Result<Box<dyn Reflect>, DbError> {
+        self.encoding.deserialize(&self.type_registry, self.migrations.resolve(type_name), data)
+            .map_err(encoding::record_error(type_name, key))
+    }
+    /// the `data` stored under `key` as the ron [`ItemData`] and [`EventData`] carry
+    fn data_to_ron(&self, key: check::RecordKey, type_name: &str, data: &[u8]) -> Result<String, DbError> {
+        self.encoding.to_ron(&self.type_registry, self.migrations.resolve(type_name), data)
+            .map_err(encoding::record_error(type_name, key))
+    }
+    fn raw_events_in(&self, keys: std::ops::RangeInclusive<EventId>) -> impl Iterator<Item = Result<(EventId, String, Vec<u8>), DbError>> + '_ {
+        self.events.range(keys).map(move |res| {
+            let (key, data) = res?;
+            let key = EventId::try_from(key.as_ref())?;
+            let name = if let Some(v) = self.type_tree.get(key)? {
+                String::from_utf8(v.to_vec())?
+            } else {return Err(DbError::NoTypeName);};
+            Ok((key, name, data))
+        })
+    }
+    fn event_data_in(&self, keys: std::ops::RangeInclusive<EventId>) -> impl Iterator<Item = Result<(EventId, EventData), DbError>> + '_ {
+        self.raw_events_in(keys).map(move |res| {
+            let (key, name, data) = res?;
+            Ok((key, EventData {
+                data: self.data_to_ron(key.into(), &name, &data)?,
+                type_name: name,
+                date: key.date_from_key(),
+                time: key.timestamp(),
+            }))
+        })
+    }
+    /// every event from `from` to `to` (both inclusive) in date order,
+    /// within a day the events without a time come first and the rest follow in time order
+    pub fn events_between(&self, from: Date, to: Date) -> impl Iterator<Item = Result<(EventId, EventData), DbError>> + '_ {
+        self.event_data_in(EventId::date_range(from, to))
+    }
+    /// the events with a time from `from` to `to` (both inclusive) in time order
+    pub fn events_between_times(&self, from: Timestamp, to: Timestamp) -> impl Iterator<Item = Result<(EventId, EventData), DbError>> + '_ {
+        self.event_data_in(EventId::time_range(from, to))
+    }
+    /// every event in `range`, see [`Database::events_between`]
+    pub fn events_in(&self, range: date::DateRange) -> impl Iterator<Item = Result<(EventId, EventData), DbError>> + '_ {
+        self.events_between(range.start(), range.end())
     }
     #[inline(always)]
-    pub fn get_item<T: serde::de::DeserializeOwned>(&self, key: ItemId) -> anyhow::Result<T> {
-        let type_name = String::from_utf8(self.type_tree.get(key)?.ok_or_else(|| DbError::NoTypeName)?.to_vec())?;
-        if type_name != std::any::type_name::<T>() {anyhow::bail!("{} != {}", type_name, std::any::type_name::<T>());}
-        let data = String::from_utf8(self.db.get(key)?.ok_or_else(|| DbError::NoData)?.to_vec())?;
-        Ok(ron::from_str(&data)?)
+    pub fn events_on(&self, date: Date) -> impl Iterator<Item = Result<(EventId, EventData), DbError>> + '_ {
+        self.events_between(date, date)
     }
-    pub fn get_item_data(&self, key: ItemId) -> Result<ItemData, DbError> {
+    /// same as [`Database::events_between`] but deserializes each event through the type registry
+    pub fn event_objs_between(&self, from: Date, to: Date) -> impl Iterator<Item = Result<(EventId, Box<dyn Reflect>), DbError>> + '_ {
+        self.raw_events_in(EventId::date_range(from, to)).map(move |res| {
+            let (key, name, data) = res?;
+            Ok((key, self.deserialize_obj(key.into(), &name, &data)?))
+        })
+    }
+    pub fn add_event_obj(&self, event: &dyn Reflect, date: Date) -> Result<EventId, DbError> {
+        self.transaction(|tx| tx.add_event_obj(event, date))
+    }
+    pub fn add_event_obj_at(&self, event: &dyn Reflect, time: Timestamp) -> Result<EventId, DbError> {
+        self.transaction(|tx| tx.add_event_obj_at(event, time))
+    }
+    pub fn insert<T: Serialize + Reflect>(&self, item: T) -> Result<ItemId, DbError> {
+        let (type_name, data) = transaction::serialize_obj(&self.type_registry, item.as_reflect())?;
+        self.add_item(&ItemData { type_name: type_name.to_string(), data })
+    }
+    pub fn get_obj(&self, key: ItemId) -> Result<Box<dyn Reflect>, DbError> {
+        let (name, data) = self.get_raw_item(key)?;
+        self.deserialize_obj(key.into(), &name, &data)
+    }
+    /// the name `T` is registered and stored under, [`DbError::TypeNotRegistered`] if it isn't in the registry
+    pub(crate) fn registered_name<T: 'static>(&self) -> Result<&'static str, DbError> {
+        self.type_registry.get(std::any::TypeId::of::<T>()).map(|registration| registration.type_name())
+            .ok_or_else(|| DbError::TypeNotRegistered(std::any::type_name::<T>().to_string()))
+    }
+    /// the name `T` is stored under, [`std::any::type_name`] if it isn't registered
+    pub(crate) fn stored_name<T: 'static>(&self) -> &'static str {
+        stored_name::<T>(&self.type_registry)
+    }
+    #[inline(always)]
+    pub fn get_item<T: serde::de::DeserializeOwned + 'static>(&self, key: ItemId) -> Result<T, DbError> {
+        let (type_name, data) = self.get_raw_item(key)?;
+        if self.migrations.resolve(&type_name) != self.stored_name::<T>() {
+            // the stored type may not be registered, so only its name goes into the error
+            let mut stored = bevy_reflect::DynamicStruct::default();
+            stored.set_name(type_name);
+            return Err(DbError::TypeMissMatch(Box::new(stored)));
+        }
+        let data = self.data_to_ron(key.into(), &type_name, &data)?;
+        ron::from_str(&data).map_err(|e| DbError::Deserialize(type_name, key.into(), e.to_string()))
+    }
+    /// stores `item` under [`Item::id`], or a new id if that is nil, and returns the id it went under;
+    /// the id isn't written back into `item`, [`Database::get`] sets it when reading
+    pub fn put<T: Item>(&self, item: &T) -> Result<ItemId, DbError> {
+        self.transaction(|tx| tx.put(item))
+    }
+    /// the `T` under `id`, [`DbError::TypeMissMatch`] if something else is stored there
+    pub fn get<T: Item>(&self, id: ItemId) -> Result<T, DbError> {
+        transaction::take_item(self.get_obj(id)?, id)
+    }
+    /// reads the `T` under `id`, runs `f` on it and writes it back as one transaction, returns what was written
+    pub fn update<T: Item, F: Fn(&mut T)>(&self, id: ItemId, f: F) -> Result<T, DbError> {
+        self.transaction(|tx| {
+            let mut item = tx.get::<T>(id)?;
+            f(&mut item);
+            let (type_name, data) = transaction::serialize_obj(&self.type_registry, item.as_reflect()).map_err(transaction::abort)?;
+            tx.insert_item_data(id, &ItemData { type_name: type_name.to_string(), data })?;
+            Ok(item)
+        })
+    }
+    fn get_raw_item(&self, key: ItemId) -> Result<(String, Vec<u8>), DbError> {
         let name = if let Some(v) = self.type_tree.get(key)? {
             String::from_utf8(v.to_vec())?
         } else {return Err(DbError::NoTypeName);};
-        let data = if let Some(v) = self.db.get(key)? {
-            String::from_utf8(v.to_vec())?
-        } else {return Err(DbError::NoData);};
-        Ok(ItemData { type_name: name, data })
+        let Some(data) = self.items.get(key)? else {return Err(DbError::NoData)};
+        Ok((name, data))
+    }
+    pub fn get_item_data(&self, key: ItemId) -> Result<ItemData, DbError> {
+        let (name, data) = self.get_raw_item(key)?;
+        Ok(ItemData { data: self.data_to_ron(key.into(), &name, &data)?, type_name: name })
     }
     pub fn get_event(&self, key: EventId) -> Result<EventData, DbError> {
         let name = if let Some(v) = self.type_tree.get(key)? {
             String::from_utf8(v.to_vec())?
         } else {return Err(DbError::NoTypeName);};
         let data = if let Some(v) = self.events.get(key)? {
-            String::from_utf8(v.to_vec())?
+            self.data_to_ron(key.into(), &name, &v)?
         } else {return Err(DbError::NoData);};
         Ok(EventData {
             type_name: name,
             data,
             date: key.date_from_key(),
+            time: key.timestamp(),
         })
     }
     pub fn add_item(&self, item: &ItemData) -> Result<ItemId, DbError> {
-        let uuid = Uuid::new_v4();
-        self.type_tree.insert(uuid, item.type_name.as_str())?;
-        self.db.insert(uuid, item.data.as_str())?;
-        Ok(ItemId(uuid))
+        let id = ItemId::new();
+        self.insert_item_data(id, item)?;
+        Ok(id)
     }
-    pub fn insert_item<T: Serialize>(&self, id: ItemId, item: T) -> anyhow::Result<()> {
-        let ser = ron::to_string(&item)?;
-        self.type_tree.insert(id, std::any::type_name::<T>())?;
-        self.db.insert(id, ser.as_str())?;
-        Ok(())
+    pub fn insert_item<T: Serialize + 'static>(&self, id: ItemId, item: T) -> Result<(), DbError> {
+        self.transaction(|tx| tx.insert_item(id, &item))
     }
     pub fn insert_item_data(&self, id: ItemId, item: &ItemData) -> Result<(), DbError> {
-        self.type_tree.insert(id, item.type_name.as_str())?;
-        self.db.insert(id, item.data.as_str())?;
-        Ok(())
+        self.transaction(|tx| tx.insert_item_data(id, item))
     }
-    pub fn new<P: AsRef<std::path::Path>>(path: P) -> Result<Database, DbError> {
-        let db = sled::open(path)?;
-        Ok(Database {
-            type_registry: type_registry(),
-            events: db.open_tree("events")?,
-            type_tree: db.open_tree("types")?,
-            db
+    /// [`Database::get_item_data`] for each of `ids`, in the same order
+    pub fn get_many(&self, ids: &[ItemId]) -> Vec<Result<ItemData, DbError>> {
+        ids.iter().map(|&id| self.get_item_data(id)).collect()
+    }
+    /// writes every item in one transaction, so either all of them land or none do;
+    /// an item's dependencies have to be stored already or come before it in `items`
+    pub fn insert_many(&self, items: &[(ItemId, ItemData)]) -> Result<(), DbError> {
+        self.transaction(|tx| {
+            for (id, item) in items {
+                tx.insert_item_data(*id, item)?;
+            }
+            Ok(())
         })
     }
+    /// opens the sled database at `path` with the default settings, see [`config::DatabaseConfig`] for the rest
+    #[cfg(feature = "sled")]
+    pub fn new<P: AsRef<std::path::Path>>(path: P) -> Result<Database, DbError> {
+        config::DatabaseConfig::new(path).open()
+    }
+    /// a database kept in `backend`, e.g. a [`storage::MemoryBackend`] for tests or the wasm client
+    pub fn with_backend<B: storage::StorageBackend + 'static>(backend: B, type_registry: bevy_reflect::TypeRegistry) -> Result<Database, DbError> {
+        let open = |name| storage::open(&backend, name);
+        let meta = open("meta")?;
+        let db = Database {
+            type_registry,
+            db: open(TREES[0])?,
+            items: open(TREES[1])?,
+            events: open(TREES[2])?,
+            type_tree: open(TREES[3])?,
+            type_index: open(TREES[4])?,
+            field_index: open(TREES[5])?,
+            dependents: open(TREES[6])?,
+            history: open(TREES[7])?,
+            tombstones: open(TREES[8])?,
+            versions: open(TREES[9])?,
+            schemas: open(TREES[10])?,
+            integrity: Default::default(),
+            migrations: Default::default(),
+            encoding: encoding::recorded(&meta)?,
+            meta,
+            backend: Box::new(backend),
+        };
+        db.ensure_indexes()?;
+        Ok(db)
+    }
 
+    /// soft deletes the item, it stays restorable with [`Database::restore`] until purged
     pub fn remove(&self, id: ItemId) -> Result<(), DbError> {
-        self.db.remove(id)?;
-        self.type_tree.remove(id)?;
-        Ok(())
+        self.transaction(|tx| tx.remove(id))
+    }
+
+    /// Items used to be split between the root tree (`insert_item`) and the events tree (`insert`);
+    /// this moves every uuid keyed record from either of them into the items tree, indexing it and starting its history.
+    /// Only needs running once on a database written before the items tree existed.
+    /// A record whose id the items tree already holds stays where it is and is reported as a conflict
+    pub fn migrate_item_storage(&self) -> Result<ItemStorageMigration, DbError> {
+        let mut report = ItemStorageMigration::default();
+        for (from_events, old) in [(false, &self.db), (true, &self.events)] {
+            for key in old.iter().keys() {
+                let key = key?;
+                if key.len() != std::mem::size_of::<Uuid>() {continue;}
+                let id = ItemId::try_from(key.as_ref())?;
+                // dependencies may be moved in any order, so they aren't checked here
+                let moved = self.transaction_with(integrity::Integrity::default(), None, |tx| {
+                    let from = if from_events {tx.events} else {tx.db};
+                    let Some(data) = from.get(&key)? else {return Ok(None)};
+                    if tx.items.get(&key)?.is_some() {return Ok(Some(false))}
+                    from.remove(&key)?;
+                    // records from before the items tree are ron, going through insert_item_data
+                    // encodes them, indexes them and records them as inserted
+                    match (tx.type_tree.remove(&key)?, String::from_utf8(data)) {
+                        (Some(type_name), Ok(data)) => {
+                            let type_name = String::from_utf8(type_name).map_err(transaction::abort)?;
+                            tx.insert_item_data(id, &ItemData { type_name, data })?;
+                        }
+                        (type_name, data) => {
+                            if let Some(type_name) = type_name {
+                                tx.type_tree.insert(&key, type_name)?;
+                            }
+                            tx.items.insert(&key, data.map_or_else(|e| e.into_bytes(), String::into_bytes))?;
+                        }
+                    }
+                    Ok(Some(true))
+                })?;
+                match moved {
+                    Some(true) => report.moved += 1,
+                    Some(false) => report.conflicts.push(id),
+                    None => {}
+                }
+            }
+        }
+        Ok(report)
     }
 }
 
-fn type_registry() -> bevy_reflect::TypeRegistry {
-    let mut type_reg = bevy_reflect::TypeRegistry::new();
-    type_reg.register::<ItemId>();
-    type_reg.register::<Vec<ItemId>>();
-    worms::register_types(&mut type_reg);
-    greenhouse::register_types(&mut type_reg);
-    type_reg
+/// What [`Database::migrate_item_storage`] did
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ItemStorageMigration {
+    pub moved: usize,
+    /// records left in the root or events tree because the items tree already has their id
+    pub conflicts: Vec<ItemId>,
+}
+
+/// every type this crate stores, a registry for [`config::DatabaseConfig::type_registry`] should start from this
+pub fn type_registry() -> bevy_reflect::TypeRegistry {
+    module::Modules::builtin().type_registry()
+}
+
+/// the name `T` is stored under in `type_registry`, [`std::any::type_name`] if it isn't registered
+pub(crate) fn stored_name<T: 'static>(type_registry: &bevy_reflect::TypeRegistry) -> &'static str {
+    type_registry.get(std::any::TypeId::of::<T>()).map_or_else(std::any::type_name::<T>, |registration| registration.type_name())
 }
 
 #[cfg(test)]
 mod test {
     use crate::{EventId, Database, plants::{Plant, PlantTypes}};
     use super::Date;
-    fn test_db() -> Database {
-        let db_options = sled::Config::new().temporary(true);
-        let db = db_options.open().unwrap();
-        Database {
-            type_tree: db.open_tree("types").unwrap(),
-            events: db.open_tree("events").unwrap(),
-            db,
-            type_registry: bevy_reflect::TypeRegistry::new(),
-        }
+    pub(crate) fn test_db() -> Database {
+        Database::with_backend(crate::storage::MemoryBackend::new(), bevy_reflect::TypeRegistry::new()).unwrap()
     }
 
-    fn test_date() -> Date {
-        Date::new_ymd(2022, 10, 01)
+    pub(crate) fn test_date() -> Date {
+        Date::new_ymd(2022, 10, 1)
+    }
+    #[test]
+    fn unregistered_round_trip() {
+        #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+        struct Note { text: String }
+        let db = test_db();
+        let id = crate::ItemId::new();
+        let note = Note { text: "water the tomatoes".into() };
+        db.insert_item(id, Note { text: note.text.clone() }).unwrap();
+        assert_eq!(db.get_item::<Note>(id).unwrap(), note);
+        assert_eq!(db.get_item_data(id).unwrap().type_name, std::any::type_name::<Note>());
     }
     #[test]
     fn date_key() {
@@ -267,7 +441,7 @@ mod test {
     #[test]
     fn database_key_test() {
         let db = test_db();
-        let date = Date::new_ymd(2022, 09, 30);
+        let date = Date::new_ymd(2022, 9, 30);
         let next = date.next();
         let key1 = db.get_next_key(date).unwrap();
         assert_eq!(key1, EventId::date_key(date).with_id(1));
@@ -301,6 +475,87 @@ mod test {
         assert_eq!(event0, event0_ref.take::<Plant>().unwrap());
         assert_eq!(event1, event1_ref.take::<Plant>().unwrap());
     }
+
+    #[test]
+    fn events_between() {
+        use crate::EventData;
+        let db = test_db();
+        let date = test_date();
+        for day in [date.prev(), date, date, date.next(), date.next().next()] {
+            db.add_event(&EventData { type_name: "test".into(), data: day.to_string(), date: day, time: None }).unwrap();
+        }
+        assert_eq!(db.events_on(date).count(), 2);
+        let range = db.events_between(date, date.next()).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(range.len(), 3);
+        assert_eq!(range[0].0, EventId::date_key(date).with_id(1));
+        assert_eq!(range[2].1.date, date.next());
+        assert_eq!(db.events_between(date.prev(), date.next().next()).count(), 5);
+    }
+
+    #[test]
+    fn timed_events() {
+        use crate::{EventData, DbError, Timestamp};
+        let db = test_db();
+        let date = test_date();
+        let at = |hour| Timestamp::new(date, hour, 0, 0).unwrap();
+        let event = |time| EventData { type_name: "test".into(), data: "1".into(), date, time };
+        let late = db.add_event(&event(Some(at(18)))).unwrap();
+        let untimed = db.add_event(&event(None)).unwrap();
+        let early = db.add_event(&event(Some(at(6)))).unwrap();
+        // untimed events lead the day, the rest sort by time whatever order they came in
+        let keys = db.events_on(date).map(|res| res.map(|(key, _)| key)).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(keys, vec![untimed, early, late]);
+        assert_eq!((early.val(), early.date_from_key(), early.timestamp()), (3, date, Some(at(6))));
+        assert!(!early.is_date_key() && untimed.timestamp().is_none());
+        assert_eq!(db.get_event(late).unwrap().time, Some(at(18)));
+        let morning = db.events_between_times(at(0), at(12)).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(morning.iter().map(|(key, event)| (*key, event.time)).collect::<Vec<_>>(), vec![(early, Some(at(6)))]);
+        assert_eq!(db.events_between(date.prev(), date).count(), 3);
+        assert!(db.events_on(date.next()).next().is_none());
+        assert!(!db.check().unwrap().iter().any(|problem| matches!(problem, crate::check::Problem::CounterBehind { .. })));
+        let wrong_day = EventData { date: date.next(), ..event(Some(at(1))) };
+        assert!(matches!(db.add_event(&wrong_day), Err(DbError::EventTimeMismatch(..))));
+    }
+
+    #[test]
+    fn item_storage() {
+        use crate::{ItemId, DbError};
+        let mut db = test_db();
+        db.type_registry.register::<ItemId>();
+        let id = db.insert(ItemId::from_u128(5)).unwrap();
+        assert_eq!(db.get_item_data(id).unwrap().type_name, "server_lib::items::ItemId");
+        assert_eq!(db.get_obj(id).unwrap().take::<ItemId>().unwrap(), ItemId::from_u128(5));
+        db.remove(id).unwrap();
+        assert!(matches!(db.get_item_data(id), Err(DbError::NoTypeName)));
+        assert!(db.get_obj(id).is_err());
+    }
+
+    #[test]
+    fn migrate_item_storage() {
+        use crate::{ItemId, ItemData, ItemStorageMigration};
+        let db = test_db();
+        let in_events = ItemId::from_u128(1);
+        let in_root = ItemId::from_u128(2);
+        db.type_tree.insert(&in_events.as_bytes()[..], "events").unwrap();
+        db.events.insert(&in_events.as_bytes()[..], "1").unwrap();
+        db.type_tree.insert(&in_root.as_bytes()[..], "root").unwrap();
+        db.db.insert(&in_root.as_bytes()[..], "2").unwrap();
+        let both = ItemId::from_u128(3);
+        db.insert_item_data(both, &ItemData { type_name: "new".into(), data: "3".into() }).unwrap();
+        db.events.insert(&both.as_bytes()[..], "old").unwrap();
+        db.get_next_key(test_date()).unwrap();
+        let report = db.migrate_item_storage().unwrap();
+        assert_eq!((report.moved, report.conflicts), (2, vec![both]));
+        assert_eq!(db.get_item_data(in_events).unwrap().data, "1");
+        assert_eq!(db.get_item_data(in_root).unwrap().data, "2");
+        assert_eq!(db.items_of_type("root").collect::<Result<Vec<_>, _>>().unwrap(), vec![in_root]);
+        assert_eq!(db.history(in_events).unwrap()[0].operation, crate::history::Operation::Insert);
+        // the conflicting record is kept for someone to look at
+        assert_eq!(db.get_item_data(both).unwrap().data, "3");
+        assert_eq!(db.events.get(both).unwrap().unwrap(), b"old");
+        assert_eq!(db.db.len(), 1);
+        assert_eq!(db.migrate_item_storage().unwrap(), ItemStorageMigration { moved: 0, conflicts: vec![both] });
+    }
 }
 
 use thiserror::*;
@@ -318,15 +573,80 @@ pub enum DbError {
     NoData,
     #[error("The type was wrong here is the reflect object")]
     TypeMissMatch(Box<dyn Reflect>),
+    #[cfg(feature = "sled")]
     #[error("sled error")]
     SledError(#[from] sled::Error),
     #[error("From Utf8Error")]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
-    #[cfg(feature = "Rocket")]
+    #[error("Utf8Error")]
+    Utf8Error(#[from] std::str::Utf8Error),
+    #[cfg(feature = "rocket")]
     #[error("Rocket Error")]
-    RocketError(#[from] rocket::Error),
+    RocketError(#[from] Box<rocket::Error>),
     #[error("Io Error")]
     IoError(#[from] std::io::Error),
     #[error("ron spanned error")]
-    RonSpannedError(#[from] ron::error::SpannedError)
-}
\ No newline at end of file
+    RonSpannedError(#[from] ron::error::SpannedError),
+    #[error("ron error")]
+    RonError(#[from] ron::Error),
+    #[error("Key is {0} bytes; expected 8 for an EventId")]
+    BadEventKey(usize),
+    #[error("Key is {0} bytes; expected 16 for an ItemId")]
+    BadItemKey(usize),
+    #[error("Type {0} is not a struct")]
+    NotAStruct(&'static str),
+    #[error("Type {0} has no field {1}")]
+    NoField(&'static str, &'static str),
+    #[error("Field {1} of {0} is not indexed")]
+    NotIndexed(&'static str, String),
+    #[error("Item {0:?} depends on {1:?} which is not in the database")]
+    MissingDependency(ItemId, ItemId),
+    #[error("Item {0:?} is still depended on by {1:?}")]
+    HasDependents(ItemId, Vec<ItemId>),
+    #[error("Item {0:?} has no revision {1}")]
+    NoRevision(ItemId, u64),
+    #[error("Item {0:?} is not in the deleted items")]
+    NotDeleted(ItemId),
+    #[error("Item {0:?} already exists")]
+    AlreadyExists(ItemId),
+    #[error("Item {0:?} was expected at version {1} but is at {2}")]
+    VersionConflict(ItemId, u64, u64),
+    #[error("Can't read {0} as a ron struct")]
+    BadRecord(String),
+    #[error("Type {0} is stored at schema version {1} but this build only knows up to {2}")]
+    SchemaTooNew(String, u32, u32),
+    #[error("{0:?} encoding failed: {1}")]
+    Encoding(encoding::Encoding, String),
+    #[error("The database already holds {0:?} encoded values")]
+    EncodingInUse(encoding::Encoding),
+    #[error("Schema upgrades need ron encoded values, not {0:?}")]
+    UpgradesNeedRon(encoding::Encoding),
+    #[error("Line {0} of the archive: {1}")]
+    BadArchive(usize, String),
+    #[error("Archive format {0} is newer than this build reads")]
+    ArchiveTooNew(u32),
+    #[error("Can't deserialize {1:?} as {0}: {2}")]
+    Deserialize(String, check::RecordKey, String),
+    #[error("The counter under {0:?} is corrupt")]
+    CorruptCounter(Vec<u8>),
+    #[error("The storage backend committed a transaction without running it")]
+    TransactionNotRun,
+    #[error("The database was opened read only")]
+    ReadOnly,
+    #[error("The indexes are older than this build, open the database writable once to rebuild them")]
+    StaleIndexes,
+    #[error("Event dated {0} has a time on {1}")]
+    EventTimeMismatch(Date, Timestamp),
+    #[error("{0} already holds as many timed events as a day can")]
+    TooManyEvents(Date),
+    #[error("{0}")]
+    DateError(#[from] date::DateError),
+}
+
+// boxed in DbError so every Result<_, DbError> doesn't carry rocket's error inline
+#[cfg(feature = "rocket")]
+impl From<rocket::Error> for DbError {
+    fn from(e: rocket::Error) -> Self {
+        DbError::RocketError(Box::new(e))
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use bevy_reflect::{prelude::*, DynamicStruct, Struct};
+use serde::{Serialize, de::DeserializeOwned};
+use crate::*;
+use crate::transaction::{TxResult, abort};
+
+// the schemas tree maps a record key to the big endian schema version its data was written with,
+// a record without an entry was written at version 0
+
+type Upgrade = Box<dyn Fn(DynamicStruct) -> Result<DynamicStruct, DbError> + Send + Sync>;
+
+/// How to bring records written by older code up to date, hand it to [`Database::set_migrations`]
+/// and run [`Database::migrate`] before reading anything.
+///
+/// A type's schema version is the number of upgrades registered for it,
+/// the first upgrade takes records from version 0 (everything written before it existed) to 1 and so on.
+#[derive(Default)]
+pub struct Migrations {
+    aliases: HashMap<String, String>,
+    upgrades: HashMap<String, Vec<Upgrade>>,
+}
+
+impl Migrations {
+    pub fn new() -> Migrations {
+        Migrations::default()
+    }
+
+    /// records stored as `old` are read and migrated as `new`, for types that were renamed or moved module
+    pub fn alias(&mut self, old: &str, new: &str) -> &mut Self {
+        self.aliases.insert(old.to_string(), new.to_string());
+        self
+    }
+
+    /// adds the next schema version of `type_name`, `upgrade` gets a record of the previous version;
+    /// fields it didn't touch are [`RonValue`]s, anything else it inserts is serialized through the type registry
+    pub fn upgrade<F>(&mut self, type_name: &str, upgrade: F) -> &mut Self
+    where F: Fn(DynamicStruct) -> Result<DynamicStruct, DbError> + Send + Sync + 'static {
+        self.upgrades.entry(type_name.to_string()).or_default().push(Box::new(upgrade));
+        self
+    }
+
+    /// the name `type_name` is registered under now, following aliases
+    pub fn resolve<'a>(&'a self, mut type_name: &'a str) -> &'a str {
+        // bounded so an alias cycle can't hang us
+        for _ in 0..=self.aliases.len() {
+            match self.aliases.get(type_name) {
+                Some(new) => type_name = new,
+                None => break,
+            }
+        }
+        type_name
+    }
+
+    /// the schema version new records of `type_name` are written with
+    /// whether any type has an upgrade, those only run on ron records
+    pub(crate) fn has_upgrades(&self) -> bool {
+        self.upgrades.values().any(|upgrades| !upgrades.is_empty())
+    }
+
+    pub fn version(&self, type_name: &str) -> u32 {
+        self.upgrades.get(self.resolve(type_name)).map_or(0, |u| u.len() as u32)
+    }
+
+    fn upgrade_data(&self, type_registry: &bevy_reflect::TypeRegistry, type_name: &str, from: u32, data: &str) -> Result<String, DbError> {
+        let steps = match self.upgrades.get(type_name) {
+            Some(steps) if (from as usize) < steps.len() => &steps[from as usize..],
+            _ => return Ok(data.to_string()),
+        };
+        let mut record = parse_struct(data)?;
+        for step in steps {
+            record = step(record)?;
+        }
+        write_struct(type_registry, &record)
+    }
+}
+
+/// A field value still in the ron it was stored as
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[reflect_value(Debug, PartialEq)]
+pub struct RonValue(pub String);
+
+impl RonValue {
+    pub fn new<T: Serialize>(value: &T) -> Result<RonValue, DbError> {
+        Ok(RonValue(ron::to_string(value)?))
+    }
+
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, DbError> {
+        Ok(ron::from_str(&self.0)?)
+    }
+}
+
+/// splits a ron struct into its top level fields, leaving every value as a [`RonValue`]
+fn parse_struct(data: &str) -> Result<DynamicStruct, DbError> {
+    let data = data.trim();
+    let bad = || DbError::BadRecord(data.to_string());
+    let open = data.find('(').ok_or_else(bad)?;
+    let name = data[..open].trim();
+    if !name.chars().all(|c| c.is_alphanumeric() || c == '_') || !data.ends_with(')') {
+        return Err(bad());
+    }
+    let mut record = DynamicStruct::default();
+    for field in split_top_level(&data[open + 1..data.len() - 1]) {
+        let field = field.trim();
+        if field.is_empty() {continue}
+        let (name, value) = field.split_once(':').ok_or_else(bad)?;
+        record.insert(name.trim(), RonValue(value.trim().to_string()));
+    }
+    Ok(record)
+}
+
+/// splits on the commas that aren't inside a string, char or brackets
+fn split_top_level(body: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in body.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&body[start..]);
+    parts
+}
+
+fn write_struct(type_registry: &bevy_reflect::TypeRegistry, record: &DynamicStruct) -> Result<String, DbError> {
+    let mut data = String::from("(");
+    for i in 0..record.field_len() {
+        let (Some(name), Some(value)) = (record.name_at(i), record.field_at(i)) else {continue};
+        data.push_str(name);
+        data.push(':');
+        match value.downcast_ref::<RonValue>() {
+            Some(raw) => data.push_str(&raw.0),
+            None => data.push_str(&transaction::serialize_obj(type_registry, value)?.1),
+        }
+        data.push(',');
+    }
+    data.push(')');
+    Ok(data)
+}
+
+fn parse_schema(raw: Option<&[u8]>) -> u32 {
+    raw.and_then(|raw| raw.try_into().ok()).map(u32::from_be_bytes).unwrap_or(0)
+}
+
+impl<'a> DbTransaction<'a> {
+    /// records that the data under `key` is at schema `version`, returns the version it was at
+    pub(crate) fn set_schema(&self, key: &[u8], version: u32) -> TxResult<u32> {
+        let old = if version == 0 {
+            self.schemas.remove(key)?
+        } else {
+            self.schemas.insert(key, &version.to_be_bytes()[..])?
+        };
+        Ok(parse_schema(old.as_deref()))
+    }
+}
+
+impl Database {
+    /// [`DbError::UpgradesNeedRon`] if `migrations` has upgrades and values aren't stored as ron,
+    /// a binary record can't be read in its old layout so an upgrade would get garbage
+    pub fn set_migrations(&mut self, migrations: Migrations) -> Result<(), DbError> {
+        if migrations.has_upgrades() && self.encoding != encoding::Encoding::Ron {
+            return Err(DbError::UpgradesNeedRon(self.encoding));
+        }
+        self.migrations = migrations;
+        Ok(())
+    }
+
+    /// the schema version the record under `key` was written with
+    pub fn schema_version<K: AsRef<[u8]>>(&self, key: K) -> Result<u32, DbError> {
+        Ok(parse_schema(self.schemas.get(key)?.as_deref()))
+    }
+
+    /// rewrites every item and event stored under an alias or an older schema version,
+    /// returns how many records were migrated
+    pub fn migrate(&self) -> Result<usize, DbError> {
+        let mut migrated = 0;
+        for res in self.type_tree.iter() {
+            let (key, stored_name) = res?;
+            let stored_name = std::str::from_utf8(&stored_name)?;
+            let type_name = self.migrations.resolve(stored_name);
+            let from = self.schema_version(&key)?;
+            let to = self.migrations.version(type_name);
+            if from > to {
+                return Err(DbError::SchemaTooNew(stored_name.to_string(), from, to));
+            }
+            if from == to && type_name == stored_name {continue}
+            self.transaction(|tx| {
+                let upgrade = |data: &[u8]| -> TxResult<String> {
+                    let data = tx.decode(type_name, data)?;
+                    self.migrations.upgrade_data(&self.type_registry, type_name, from, &data).map_err(abort)
+                };
+                if let Ok(id) = ItemId::try_from(key.as_ref()) {
+                    let Some(data) = tx.items.get(&key)? else {return Ok(())};
+                    let data = upgrade(&data)?;
+                    tx.insert_item_data(id, &ItemData { type_name: type_name.to_string(), data })
+                } else {
+                    EventId::try_from(key.as_ref()).map_err(abort)?;
+                    let Some(data) = tx.events.get(&key)? else {return Ok(())};
+                    let data = upgrade(&data)?;
+                    tx.type_tree.insert(&key, type_name)?;
+                    tx.events.insert(&key, tx.encode(type_name, &data)?)?;
+                    tx.set_schema(&key, to)?;
+                    Ok(())
+                }
+            })?;
+            migrated += 1;
+        }
+        Ok(migrated)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{test_db, test_date};
+    #[test]
+    fn migrate() {
+        use bevy_reflect::{DynamicStruct, GetField};
+        use crate::{ItemData, ItemId, DbError, EventData, migration::{Migrations, RonValue}};
+        let mut db = test_db();
+        let id = ItemId::from_u128(1);
+        db.insert_item_data(id, &ItemData { type_name: "old::Crate".into(), data: "(crop:\"Tomato, Cherry\",kilos:(3))".into() }).unwrap();
+        let event = db.add_event(&EventData { type_name: "old::Crate".into(), data: "(crop:\"Basil\",kilos:(1))".into(), date: test_date(), time: None }).unwrap();
+        let mut migrations = Migrations::new();
+        migrations.alias("old::Crate", "new::Crate").upgrade("new::Crate", |old| {
+            let mut new = DynamicStruct::default();
+            new.insert("crop", old.get_field::<RonValue>("crop").unwrap().clone());
+            let (kilos,): (u32,) = old.get_field::<RonValue>("kilos").unwrap().parse()?;
+            new.insert("grams", kilos * 1000);
+            Ok(new)
+        });
+        db.set_migrations(migrations).unwrap();
+        assert_eq!(db.schema_version(id).unwrap(), 0);
+        assert_eq!(db.migrate().unwrap(), 2);
+        let expected = ItemData { type_name: "new::Crate".into(), data: "(crop:\"Tomato, Cherry\",grams:3000,)".into() };
+        assert_eq!(db.get_item_data(id).unwrap(), expected);
+        assert_eq!(db.get_event(event).unwrap().data, "(crop:\"Basil\",grams:1000,)");
+        assert_eq!(db.schema_version(id).unwrap(), 1);
+        assert_eq!(db.migrate().unwrap(), 0);
+        db.remove(id).unwrap();
+        db.restore(id).unwrap();
+        assert_eq!(db.schema_version(id).unwrap(), 1);
+        db.set_migrations(Migrations::new()).unwrap();
+        assert!(matches!(db.migrate(), Err(DbError::SchemaTooNew(_, 1, 0))));
+    }
+
+    #[test]
+    fn migrate_under_bincode() {
+        use crate::{ItemData, ItemId, DbError, encoding::Encoding, migration::Migrations, plants::{Plant, PlantTypes}};
+        let plant_name = std::any::type_name::<Plant>();
+        let mut upgrades = Migrations::new();
+        upgrades.upgrade(plant_name, Ok);
+        let mut db = test_db();
+        db.type_registry.register::<Plant>();
+        db.type_registry.register::<PlantTypes>();
+        db.set_migrations(upgrades).unwrap();
+        assert!(matches!(db.set_encoding(Encoding::Bincode), Err(DbError::UpgradesNeedRon(Encoding::Bincode))));
+        db.set_migrations(Migrations::new()).unwrap();
+        db.set_encoding(Encoding::Bincode).unwrap();
+        let mut upgrades = Migrations::new();
+        upgrades.upgrade(plant_name, Ok);
+        assert!(matches!(db.set_migrations(upgrades), Err(DbError::UpgradesNeedRon(Encoding::Bincode))));
+        // a rename keeps the layout, so it can be migrated in place
+        let mut renamed = Migrations::new();
+        renamed.alias("old::Plant", plant_name);
+        db.set_migrations(renamed).unwrap();
+        let id = ItemId::from_u128(1);
+        db.insert_item_data(id, &ItemData { type_name: "old::Plant".into(), data: ron::to_string(&Plant::test(0)).unwrap() }).unwrap();
+        assert_eq!(db.migrate().unwrap(), 1);
+        assert_eq!(db.get_item_data(id).unwrap().type_name, plant_name);
+        assert_eq!(crate::Item::id(&db.get::<Plant>(id).unwrap()), id);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::*;
+
+/// A group of item types plus whatever serves them, so a downstream crate can plug its own items
+/// into the [`Database`] and the client without touching this crate
+pub trait Module: Send + Sync {
+    /// tells modules apart, the server mounts a module's routes under `/<name>`
+    fn name(&self) -> &'static str;
+
+    /// registers every type the module stores, including the types of their fields
+    fn register_types(&self, type_reg: &mut bevy_reflect::TypeRegistry);
+
+    #[cfg(feature = "rocket")]
+    fn routes(&self) -> Vec<::rocket::Route> {
+        Vec::new()
+    }
+
+    /// the module's top level views, each with a name the client can put in its navigation
+    #[cfg(feature = "yew")]
+    fn components(&self) -> Vec<(&'static str, ::yew::Html)> {
+        Vec::new()
+    }
+}
+
+/// The set of [`Module`]s an application is made of;
+/// build the [`Database`] (through [`DatabaseConfig::modules`](crate::config::DatabaseConfig::modules))
+/// and the client's `CallbackReg` from the same set so they agree on the types
+#[derive(Default)]
+pub struct Modules(Vec<Box<dyn Module>>);
+
+impl Modules {
+    /// no modules at all, the registry will only know [`ItemId`]
+    pub fn new() -> Modules {
+        Modules::default()
+    }
+
+    /// the modules this crate ships
+    pub fn builtin() -> Modules {
+        Modules::new().with(worms::Worms).with(plants::Plants).with(greenhouse::Greenhouse)
+    }
+
+    pub fn with<M: Module + 'static>(mut self, module: M) -> Self {
+        self.0.push(Box::new(module));
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Module> {
+        self.0.iter().map(|module| &**module)
+    }
+
+    /// a registry holding every module's types
+    pub fn type_registry(&self) -> bevy_reflect::TypeRegistry {
+        let mut type_reg = bevy_reflect::TypeRegistry::new();
+        type_reg.register::<ItemId>();
+        type_reg.register::<Vec<ItemId>>();
+        for module in self.iter() {
+            module.register_types(&mut type_reg);
+        }
+        type_reg
+    }
+
+    /// every module's routes with the path to mount them at
+    #[cfg(feature = "rocket")]
+    pub fn routes(&self) -> Vec<(String, Vec<::rocket::Route>)> {
+        self.iter().map(|module| (format!("/{}", module.name()), module.routes())).collect()
+    }
+
+    #[cfg(feature = "yew")]
+    pub fn components(&self) -> Vec<(&'static str, ::yew::Html)> {
+        self.iter().flat_map(|module| module.components()).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn modules() {
+        use crate::{config::DatabaseConfig, module::{Module, Modules}, plants::Plant, greenhouse::Crate};
+        struct Nursery;
+        impl Module for Nursery {
+            fn name(&self) -> &'static str {
+                "nursery"
+            }
+            fn register_types(&self, type_reg: &mut bevy_reflect::TypeRegistry) {
+                crate::plants::register_types(type_reg);
+            }
+        }
+        assert!(crate::type_registry().get_with_name(std::any::type_name::<Plant>()).is_some());
+        let db = DatabaseConfig::in_memory().modules(&Modules::new().with(Nursery)).open().unwrap();
+        let plant = db.put(&Plant::test(0)).unwrap();
+        assert_eq!(db.get::<Plant>(plant).unwrap(), Plant::test(0));
+        assert!(db.type_registry.get_with_name(std::any::type_name::<Crate>()).is_none());
+    }
+}
@@ -3,8 +3,28 @@ use serde::{Serialize, Deserialize};
 use strum::{IntoStaticStr, EnumIter};
 use bevy_reflect::prelude::*;
 use crate::*;
+use crate::items::ReflectToItem;
 use derive_more::Display;
 
+pub fn register_types(type_reg: &mut bevy_reflect::TypeRegistry) {
+    type_reg.register::<PlantTypes>();
+    type_reg.register::<Stage>();
+    type_reg.register::<Plant>();
+    type_reg.register::<SeedTray>();
+    type_reg.register::<Event>();
+}
+
+pub struct Plants;
+
+impl crate::module::Module for Plants {
+    fn name(&self) -> &'static str {
+        "plants"
+    }
+    fn register_types(&self, type_reg: &mut bevy_reflect::TypeRegistry) {
+        register_types(type_reg);
+    }
+}
+
 #[derive(Debug, FromStr, IntoStaticStr, EnumIter, Serialize, Deserialize, Clone, Reflect, Display, Copy, PartialEq)]
 #[reflect_value(Serialize, Deserialize)]
 pub enum PlantTypes {
@@ -38,7 +58,7 @@ enum Event {
 }
 
 #[derive(Debug, Serialize, Deserialize, Reflect, PartialEq)]
-#[reflect(Serialize, Deserialize, PartialEq)]
+#[reflect(Serialize, Deserialize, PartialEq, ToItem)]
 pub struct Plant {
     id: ItemId,
     plant_type: PlantTypes,
@@ -58,8 +78,9 @@ impl Item for Plant {
     }
 }
 
+/// items other items can be in, found through [`ReflectLocation`] in the type registry
 #[reflect_trait]
-trait Location {
+pub trait Location {
     fn location_of(&self, find: ItemId) -> Option<String>;
 }
 
@@ -0,0 +1,733 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::cell::RefCell;
+use std::ops::{Bound, RangeBounds};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use futures::Stream;
+use crate::DbError;
+use crate::transaction::{TxError, TxResult};
+
+/// the name of the tree that isn't opened by name, for sled that is the root of the database
+pub const DEFAULT_TREE: &str = "__sled__default";
+
+/// Where a [`Database`](crate::Database) keeps its bytes, every tree is an ordered map of byte keys to byte values
+pub trait StorageBackend: Send + Sync {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn StorageTree>, DbError>;
+    /// the trees that exist, opening one with [`StorageBackend::open_tree`] or a transaction creates it
+    fn tree_names(&self) -> Result<Vec<String>, DbError>;
+    /// runs `f` with a view of each tree in `trees` (same order), as one atomic write;
+    /// `f` may be called more than once if another writer conflicts with it
+    fn transaction(&self, trees: &[&str], f: &dyn Fn(&[&dyn StorageTxTree]) -> TxResult<()>) -> Result<(), DbError>;
+}
+
+impl<B: StorageBackend + ?Sized> StorageBackend for Box<B> {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn StorageTree>, DbError> {
+        (**self).open_tree(name)
+    }
+    fn tree_names(&self) -> Result<Vec<String>, DbError> {
+        (**self).tree_names()
+    }
+    fn transaction(&self, trees: &[&str], f: &dyn Fn(&[&dyn StorageTxTree]) -> TxResult<()>) -> Result<(), DbError> {
+        (**self).transaction(trees, f)
+    }
+}
+
+/// lets several [`Database`](crate::Database)s, one after the other, open the same backend
+impl<B: StorageBackend + ?Sized> StorageBackend for Arc<B> {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn StorageTree>, DbError> {
+        (**self).open_tree(name)
+    }
+    fn tree_names(&self) -> Result<Vec<String>, DbError> {
+        (**self).tree_names()
+    }
+    fn transaction(&self, trees: &[&str], f: &dyn Fn(&[&dyn StorageTxTree]) -> TxResult<()>) -> Result<(), DbError> {
+        (**self).transaction(trees, f)
+    }
+}
+
+pub trait StorageTree: Send + Sync {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError>;
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>, DbError>;
+    fn remove(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError>;
+    /// every entry with a key in `range`, in key order
+    fn range(&self, range: (Bound<Vec<u8>>, Bound<Vec<u8>>)) -> Iter;
+    fn clear(&self) -> Result<(), DbError>;
+    /// every write to a key starting with `prefix` from now on, including those made by transactions
+    fn watch_prefix(&self, prefix: &[u8]) -> Watcher;
+}
+
+/// a tree inside a [`StorageBackend::transaction`], writes only land if the whole transaction does
+pub trait StorageTxTree {
+    fn get(&self, key: &[u8]) -> TxResult<Option<Vec<u8>>>;
+    fn insert(&self, key: &[u8], value: &[u8]) -> TxResult<Option<Vec<u8>>>;
+    fn remove(&self, key: &[u8]) -> TxResult<Option<Vec<u8>>>;
+    /// adds one to the counter under `key` and returns the new count, the first call returns 1
+    fn increment(&self, key: &[u8]) -> TxResult<u64> {
+        let next = match self.get(key)? {
+            Some(raw) => decode_counter(key, &raw).map_err(crate::transaction::abort)?,
+            None => 0,
+        } + 1;
+        self.insert(key, &encode_counter(next))?;
+        Ok(next)
+    }
+}
+
+/// a key and its value
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+pub struct Iter(Box<dyn Iterator<Item = Result<Entry, DbError>>>);
+
+impl Iterator for Iter {
+    type Item = Result<Entry, DbError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl Iter {
+    pub fn keys(self) -> impl Iterator<Item = Result<Vec<u8>, DbError>> {
+        self.map(|res| res.map(|(key, _)| key))
+    }
+}
+
+/// A write seen by a [`Watcher`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    Insert(Vec<u8>, Vec<u8>),
+    Remove(Vec<u8>),
+}
+
+/// the writes under a prefix in the order they landed, ends when the backend goes away
+pub type Watcher = Box<dyn Stream<Item = WatchEvent> + Send + Unpin>;
+
+/// counters are stored as a big endian varint with bincode
+pub(crate) fn encode_counter(count: u64) -> Vec<u8> {
+    use bincode::Options;
+    // writing a u64 into a vec has nothing that can fail
+    bincode::options().with_big_endian().serialize(&count).unwrap_or_default()
+}
+
+/// the counter stored under `key`, [`DbError::CorruptCounter`] rather than starting over at 0
+/// since that would hand out ids that are already taken
+pub(crate) fn decode_counter(key: &[u8], raw: &[u8]) -> Result<u64, DbError> {
+    use bincode::Options;
+    bincode::options().with_big_endian().deserialize(raw).map_err(|_| DbError::CorruptCounter(key.to_vec()))
+}
+
+fn owned_bound<K: AsRef<[u8]>>(bound: Bound<&K>) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.as_ref().to_vec()),
+        Bound::Excluded(key) => Bound::Excluded(key.as_ref().to_vec()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// the smallest key greater than everything starting with `prefix`, `None` if there is no such key
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// A handle on one tree of the backend
+#[derive(Clone)]
+pub struct Tree(Arc<dyn StorageTree>);
+
+impl Tree {
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>, DbError> {
+        self.0.get(key.as_ref())
+    }
+    pub fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<Option<Vec<u8>>, DbError> {
+        self.0.insert(key.as_ref(), value.as_ref())
+    }
+    pub fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>, DbError> {
+        self.0.remove(key.as_ref())
+    }
+    pub fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range: R) -> Iter {
+        self.0.range((owned_bound(range.start_bound()), owned_bound(range.end_bound())))
+    }
+    pub fn scan_prefix<P: AsRef<[u8]>>(&self, prefix: P) -> Iter {
+        let prefix = prefix.as_ref();
+        let end = prefix_end(prefix).map_or(Bound::Unbounded, Bound::Excluded);
+        self.0.range((Bound::Included(prefix.to_vec()), end))
+    }
+    pub fn iter(&self) -> Iter {
+        self.0.range((Bound::Unbounded, Bound::Unbounded))
+    }
+    pub fn clear(&self) -> Result<(), DbError> {
+        self.0.clear()
+    }
+    pub fn watch_prefix<P: AsRef<[u8]>>(&self, prefix: P) -> Watcher {
+        self.0.watch_prefix(prefix.as_ref())
+    }
+    /// counts by iterating, so only cheap on small trees
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+/// A handle on one tree inside a transaction
+#[derive(Clone, Copy)]
+pub struct TxTree<'a>(&'a dyn StorageTxTree);
+
+impl<'a> TxTree<'a> {
+    pub(crate) fn new(tree: &'a dyn StorageTxTree) -> TxTree<'a> {
+        TxTree(tree)
+    }
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> TxResult<Option<Vec<u8>>> {
+        self.0.get(key.as_ref())
+    }
+    pub fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> TxResult<Option<Vec<u8>>> {
+        self.0.insert(key.as_ref(), value.as_ref())
+    }
+    pub fn remove<K: AsRef<[u8]>>(&self, key: K) -> TxResult<Option<Vec<u8>>> {
+        self.0.remove(key.as_ref())
+    }
+    pub fn increment<K: AsRef<[u8]>>(&self, key: K) -> TxResult<u64> {
+        self.0.increment(key.as_ref())
+    }
+}
+
+/// opens a tree of `backend` as a [`Tree`]
+pub(crate) fn open(backend: &dyn StorageBackend, name: &str) -> Result<Tree, DbError> {
+    Ok(Tree(backend.open_tree(name)?))
+}
+
+/// Stores everything in a [`sled::Db`] on disk
+#[cfg(feature = "sled")]
+pub struct SledBackend {
+    db: sled::Db,
+}
+
+#[cfg(feature = "sled")]
+impl SledBackend {
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<SledBackend, DbError> {
+        Ok(SledBackend { db: sled::open(path)? })
+    }
+
+    fn tree(&self, name: &str) -> Result<sled::Tree, DbError> {
+        if name == DEFAULT_TREE {
+            Ok((*self.db).clone())
+        } else {
+            Ok(self.db.open_tree(name)?)
+        }
+    }
+}
+
+#[cfg(feature = "sled")]
+impl From<sled::Db> for SledBackend {
+    fn from(db: sled::Db) -> Self {
+        SledBackend { db }
+    }
+}
+
+#[cfg(feature = "sled")]
+impl StorageBackend for SledBackend {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn StorageTree>, DbError> {
+        Ok(Arc::new(self.tree(name)?))
+    }
+
+    fn tree_names(&self) -> Result<Vec<String>, DbError> {
+        Ok(self.db.tree_names().iter().map(|name| String::from_utf8_lossy(name).into_owned()).collect())
+    }
+
+    fn transaction(&self, trees: &[&str], f: &dyn Fn(&[&dyn StorageTxTree]) -> TxResult<()>) -> Result<(), DbError> {
+        use sled::Transactional;
+        use sled::transaction::{ConflictableTransactionError, TransactionError};
+        let trees = trees.iter().map(|name| self.tree(name)).collect::<Result<Vec<_>, _>>()?;
+        trees[..].transaction(|views| {
+            let views: Vec<&dyn StorageTxTree> = views.iter().map(|view| view as &dyn StorageTxTree).collect();
+            f(&views).map_err(|e| match e {
+                TxError::Abort(e) => ConflictableTransactionError::Abort(e),
+                TxError::Conflict => ConflictableTransactionError::Conflict,
+                TxError::Storage(DbError::SledError(e)) => ConflictableTransactionError::Storage(e),
+                // a storage error from outside sled can't be retried, so it ends the transaction
+                TxError::Storage(e) => ConflictableTransactionError::Abort(e),
+            })
+        }).map_err(|e| match e {
+            TransactionError::Abort(e) => e,
+            TransactionError::Storage(e) => DbError::SledError(e),
+        })
+    }
+}
+
+#[cfg(feature = "sled")]
+impl StorageTree for sled::Tree {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(sled::Tree::get(self, key)?.map(|v| v.to_vec()))
+    }
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(sled::Tree::insert(self, key, value)?.map(|v| v.to_vec()))
+    }
+    fn remove(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(sled::Tree::remove(self, key)?.map(|v| v.to_vec()))
+    }
+    fn range(&self, range: (Bound<Vec<u8>>, Bound<Vec<u8>>)) -> Iter {
+        Iter(Box::new(sled::Tree::range(self, range).map(|res| {
+            let (key, value) = res?;
+            Ok((key.to_vec(), value.to_vec()))
+        })))
+    }
+    fn clear(&self) -> Result<(), DbError> {
+        Ok(sled::Tree::clear(self)?)
+    }
+    fn watch_prefix(&self, prefix: &[u8]) -> Watcher {
+        Box::new(SledWatcher(sled::Tree::watch_prefix(self, prefix)))
+    }
+}
+
+#[cfg(feature = "sled")]
+struct SledWatcher(sled::Subscriber);
+
+#[cfg(feature = "sled")]
+impl Stream for SledWatcher {
+    type Item = WatchEvent;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<WatchEvent>> {
+        use std::future::Future;
+        Pin::new(&mut self.0).poll(cx).map(|event| event.map(|event| match event {
+            sled::Event::Insert { key, value } => WatchEvent::Insert(key.to_vec(), value.to_vec()),
+            sled::Event::Remove { key } => WatchEvent::Remove(key.to_vec()),
+        }))
+    }
+}
+
+#[cfg(feature = "sled")]
+impl StorageTxTree for sled::transaction::TransactionalTree {
+    fn get(&self, key: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        Ok(sled::transaction::TransactionalTree::get(self, key).map_err(sled_tx_error)?.map(|v| v.to_vec()))
+    }
+    fn insert(&self, key: &[u8], value: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        Ok(sled::transaction::TransactionalTree::insert(self, key, value).map_err(sled_tx_error)?.map(|v| v.to_vec()))
+    }
+    fn remove(&self, key: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        Ok(sled::transaction::TransactionalTree::remove(self, key).map_err(sled_tx_error)?.map(|v| v.to_vec()))
+    }
+}
+
+#[cfg(feature = "sled")]
+fn sled_tx_error(e: sled::transaction::UnabortableTransactionError) -> TxError {
+    match e {
+        sled::transaction::UnabortableTransactionError::Conflict => TxError::Conflict,
+        sled::transaction::UnabortableTransactionError::Storage(e) => TxError::Storage(DbError::SledError(e)),
+    }
+}
+
+/// Wraps another backend and refuses every write with [`DbError::ReadOnly`], reads and transactions that only read still work;
+/// a tree the inner backend doesn't have reads as empty and isn't created
+pub struct ReadOnlyBackend<B>(pub B);
+
+impl<B: StorageBackend> ReadOnlyBackend<B> {
+    fn exists(&self, name: &str) -> Result<bool, DbError> {
+        Ok(self.0.tree_names()?.iter().any(|existing| existing == name))
+    }
+}
+
+impl<B: StorageBackend> StorageBackend for ReadOnlyBackend<B> {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn StorageTree>, DbError> {
+        let tree: Arc<dyn StorageTree> = if self.exists(name)? {self.0.open_tree(name)?} else {Arc::new(MemoryTree::default())};
+        Ok(Arc::new(ReadOnlyTree(tree)))
+    }
+
+    fn tree_names(&self) -> Result<Vec<String>, DbError> {
+        self.0.tree_names()
+    }
+
+    fn transaction(&self, trees: &[&str], f: &dyn Fn(&[&dyn StorageTxTree]) -> TxResult<()>) -> Result<(), DbError> {
+        let names = self.0.tree_names()?;
+        let existing: Vec<&str> = trees.iter().copied().filter(|name| names.iter().any(|existing| existing == name)).collect();
+        self.0.transaction(&existing, &|views| {
+            // the inner views line up with the trees that exist, the rest get an empty one
+            let mut views = views.iter().copied();
+            let views: Vec<ReadOnlyTxTree> = trees.iter()
+                .map(|name| ReadOnlyTxTree(if existing.contains(name) {views.next().unwrap_or(&MissingTxTree)} else {&MissingTxTree}))
+                .collect();
+            let views: Vec<&dyn StorageTxTree> = views.iter().map(|view| view as &dyn StorageTxTree).collect();
+            f(&views)
+        })
+    }
+}
+
+struct ReadOnlyTree(Arc<dyn StorageTree>);
+
+impl StorageTree for ReadOnlyTree {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        self.0.get(key)
+    }
+    fn insert(&self, _: &[u8], _: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Err(DbError::ReadOnly)
+    }
+    fn remove(&self, _: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Err(DbError::ReadOnly)
+    }
+    fn range(&self, range: (Bound<Vec<u8>>, Bound<Vec<u8>>)) -> Iter {
+        self.0.range(range)
+    }
+    fn clear(&self) -> Result<(), DbError> {
+        Err(DbError::ReadOnly)
+    }
+    fn watch_prefix(&self, prefix: &[u8]) -> Watcher {
+        self.0.watch_prefix(prefix)
+    }
+}
+
+struct ReadOnlyTxTree<'a>(&'a dyn StorageTxTree);
+
+impl<'a> StorageTxTree for ReadOnlyTxTree<'a> {
+    fn get(&self, key: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        self.0.get(key)
+    }
+    fn insert(&self, _: &[u8], _: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        Err(crate::transaction::abort(DbError::ReadOnly))
+    }
+    fn remove(&self, _: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        Err(crate::transaction::abort(DbError::ReadOnly))
+    }
+}
+
+/// a tree a [`ReadOnlyBackend`] transaction doesn't open, so it doesn't get created
+struct MissingTxTree;
+
+impl StorageTxTree for MissingTxTree {
+    fn get(&self, _: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        Ok(None)
+    }
+    fn insert(&self, _: &[u8], _: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        Err(crate::transaction::abort(DbError::ReadOnly))
+    }
+    fn remove(&self, _: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        Err(crate::transaction::abort(DbError::ReadOnly))
+    }
+}
+
+type Map = BTreeMap<Vec<u8>, Vec<u8>>;
+/// a watched prefix and the queue its watcher reads from
+type Watch = (Vec<u8>, Arc<Mutex<WatchQueue>>);
+
+/// Keeps everything in memory and loses it on drop, for tests and clients without a disk
+#[derive(Default)]
+pub struct MemoryBackend {
+    trees: Mutex<HashMap<String, Arc<MemoryTree>>>,
+}
+
+#[derive(Default)]
+pub struct MemoryTree {
+    map: Mutex<Map>,
+    watchers: Mutex<Vec<Watch>>,
+}
+
+impl MemoryTree {
+    fn lock(&self) -> MutexGuard<'_, Map> {
+        // every write is a single map operation, so a panicking holder can't leave the map half written
+        self.map.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// hands a write to every watcher of a prefix of `key`, called with the map still locked so they see writes in order
+    fn notify(&self, key: &[u8], value: Option<&[u8]>) {
+        let mut watchers = self.watchers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // the watcher is the only other owner of its queue, once it's dropped nobody is listening
+        watchers.retain(|(_, queue)| Arc::strong_count(queue) > 1);
+        for (prefix, queue) in watchers.iter() {
+            if !key.starts_with(prefix) {continue}
+            let mut queue = queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            queue.events.push_back(match value {
+                Some(value) => WatchEvent::Insert(key.to_vec(), value.to_vec()),
+                None => WatchEvent::Remove(key.to_vec()),
+            });
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct WatchQueue {
+    events: VecDeque<WatchEvent>,
+    waker: Option<Waker>,
+}
+
+struct MemoryWatcher(Arc<Mutex<WatchQueue>>);
+
+impl Stream for MemoryWatcher {
+    type Item = WatchEvent;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<WatchEvent>> {
+        let mut queue = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match queue.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => {
+                queue.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl MemoryBackend {
+    pub fn new() -> MemoryBackend {
+        MemoryBackend::default()
+    }
+
+    fn tree(&self, name: &str) -> Arc<MemoryTree> {
+        let mut trees = self.trees.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        trees.entry(name.to_string()).or_default().clone()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn StorageTree>, DbError> {
+        Ok(self.tree(name))
+    }
+
+    fn tree_names(&self) -> Result<Vec<String>, DbError> {
+        Ok(self.trees.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).keys().cloned().collect())
+    }
+
+    fn transaction(&self, trees: &[&str], f: &dyn Fn(&[&dyn StorageTxTree]) -> TxResult<()>) -> Result<(), DbError> {
+        let mut names: Vec<&str> = trees.to_vec();
+        // locking in name order means two transactions can't each hold a tree the other is waiting for
+        names.sort_unstable();
+        names.dedup();
+        let handles: Vec<_> = names.iter().map(|name| self.tree(name)).collect();
+        // like sled, `f` runs without holding the trees so it can read the database outside the transaction;
+        // whatever it read is checked again under the locks and it runs again if any of it changed
+        loop {
+            let views: Vec<MemoryTxTree> = handles.iter().map(|tree| MemoryTxTree { tree, reads: RefCell::default(), writes: RefCell::default() }).collect();
+            let ordered: Vec<&dyn StorageTxTree> = trees.iter()
+                .map(|name| &views[names.binary_search(name).unwrap_or_default()] as &dyn StorageTxTree)
+                .collect();
+            match f(&ordered) {
+                Ok(()) => {},
+                Err(TxError::Abort(e) | TxError::Storage(e)) => return Err(e),
+                Err(TxError::Conflict) => continue,
+            }
+            let mut maps: Vec<MutexGuard<Map>> = handles.iter().map(|tree| tree.lock()).collect();
+            let unchanged = maps.iter().zip(&views)
+                .all(|(map, view)| view.reads.borrow().iter().all(|(key, value)| map.get(key) == value.as_ref()));
+            if !unchanged {continue}
+            for ((map, tree), view) in maps.iter_mut().zip(&handles).zip(views) {
+                for (key, value) in view.writes.into_inner() {
+                    match value {
+                        Some(value) => {
+                            tree.notify(&key, Some(&value));
+                            map.insert(key, value);
+                        }
+                        // the same as MemoryTree::remove, only a key that was there is reported
+                        None => if map.remove(&key).is_some() {
+                            tree.notify(&key, None);
+                        },
+                    }
+                }
+            }
+            return Ok(());
+        }
+    }
+}
+
+impl StorageTree for MemoryTree {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(self.lock().get(key).cloned())
+    }
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        let mut map = self.lock();
+        self.notify(key, Some(value));
+        Ok(map.insert(key.to_vec(), value.to_vec()))
+    }
+    fn remove(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        let mut map = self.lock();
+        let old = map.remove(key);
+        if old.is_some() {
+            self.notify(key, None);
+        }
+        Ok(old)
+    }
+    fn range(&self, range: (Bound<Vec<u8>>, Bound<Vec<u8>>)) -> Iter {
+        // a snapshot, so writing while iterating can't deadlock
+        let entries: Vec<_> = self.lock().range(range).map(|(key, value)| Ok((key.clone(), value.clone()))).collect();
+        Iter(Box::new(entries.into_iter()))
+    }
+    fn clear(&self) -> Result<(), DbError> {
+        let mut map = self.lock();
+        for key in map.keys() {
+            self.notify(key, None);
+        }
+        map.clear();
+        Ok(())
+    }
+    fn watch_prefix(&self, prefix: &[u8]) -> Watcher {
+        let queue = Arc::new(Mutex::new(WatchQueue::default()));
+        let mut watchers = self.watchers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        watchers.push((prefix.to_vec(), queue.clone()));
+        Box::new(MemoryWatcher(queue))
+    }
+}
+
+/// a tree plus what the transaction read from it and everything written to it so far, `None` marking a removal
+struct MemoryTxTree<'a> {
+    tree: &'a MemoryTree,
+    reads: RefCell<HashMap<Vec<u8>, Option<Vec<u8>>>>,
+    writes: RefCell<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+}
+
+impl<'a> StorageTxTree for MemoryTxTree<'a> {
+    fn get(&self, key: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        if let Some(value) = self.writes.borrow().get(key) {
+            return Ok(value.clone());
+        }
+        // a key read twice gives the same value both times, the commit checks it is still that value
+        let mut reads = self.reads.borrow_mut();
+        Ok(reads.entry(key.to_vec()).or_insert_with(|| self.tree.lock().get(key).cloned()).clone())
+    }
+    fn insert(&self, key: &[u8], value: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        let old = self.get(key)?;
+        self.writes.borrow_mut().insert(key.to_vec(), Some(value.to_vec()));
+        Ok(old)
+    }
+    fn remove(&self, key: &[u8]) -> TxResult<Option<Vec<u8>>> {
+        let old = self.get(key)?;
+        self.writes.borrow_mut().insert(key.to_vec(), None);
+        Ok(old)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use futures::executor::block_on_stream;
+    use crate::storage::*;
+    use crate::transaction::abort;
+
+    /// every backend there is, so they can be held to the same behaviour
+    fn backends() -> Vec<Box<dyn StorageBackend>> {
+        vec![
+            Box::new(MemoryBackend::new()),
+            #[cfg(feature = "sled")]
+            Box::new(SledBackend::from(sled::Config::new().temporary(true).open().unwrap())),
+        ]
+    }
+
+    #[test]
+    fn abort_rolls_back() {
+        for backend in backends() {
+            let tree = backend.open_tree("a").unwrap();
+            tree.insert(b"kept", b"1").unwrap();
+            let res = backend.transaction(&["a", "b"], &|views| {
+                views[0].insert(b"kept", b"2")?;
+                views[1].insert(b"new", b"1")?;
+                assert_eq!(views[0].get(b"kept")?, Some(b"2".to_vec()));
+                Err(abort(DbError::NoData))
+            });
+            assert!(matches!(res, Err(DbError::NoData)));
+            assert_eq!(tree.get(b"kept").unwrap(), Some(b"1".to_vec()));
+            assert_eq!(backend.open_tree("b").unwrap().get(b"new").unwrap(), None);
+            backend.transaction(&["a"], &|views| {
+                views[0].increment(b"count")?;
+                views[0].increment(b"count")?;
+                Ok(())
+            }).unwrap();
+            assert_eq!(decode_counter(b"count", &tree.get(b"count").unwrap().unwrap()).unwrap(), 2);
+        }
+    }
+
+    #[test]
+    fn watch() {
+        for backend in backends() {
+            let tree = backend.open_tree("a").unwrap();
+            let mut watcher = block_on_stream(tree.watch_prefix(b"k"));
+            tree.insert(b"other", b"0").unwrap();
+            tree.insert(b"k1", b"1").unwrap();
+            backend.transaction(&["a"], &|views| {
+                views[0].insert(b"k2", b"2")?;
+                views[0].remove(b"k1")?;
+                Ok(())
+            }).unwrap();
+            tree.remove(b"k2").unwrap();
+            assert_eq!(watcher.next(), Some(WatchEvent::Insert(b"k1".to_vec(), b"1".to_vec())));
+            let mut committed = vec![watcher.next().unwrap(), watcher.next().unwrap()];
+            committed.sort_by_key(|event| matches!(event, WatchEvent::Remove(_)));
+            assert_eq!(committed, [WatchEvent::Insert(b"k2".to_vec(), b"2".to_vec()), WatchEvent::Remove(b"k1".to_vec())]);
+            assert_eq!(watcher.next(), Some(WatchEvent::Remove(b"k2".to_vec())));
+            let (start, end) = (Bound::Included(b"a".to_vec()), Bound::Unbounded);
+            assert_eq!(tree.range((start, end)).keys().map(Result::unwrap).collect::<Vec<_>>(), [b"other".to_vec()]);
+        }
+    }
+
+    #[test]
+    fn memory_transaction_reads_outside() {
+        let backend = MemoryBackend::new();
+        let tree = backend.open_tree("tree").unwrap();
+        tree.insert(b"key", b"1").unwrap();
+        let runs = Cell::new(0);
+        backend.transaction(&["tree"], &|views| {
+            runs.set(runs.get() + 1);
+            let seen = views[0].get(b"key")?;
+            assert_eq!(tree.get(b"key").map_err(abort)?, seen);
+            // a write the transaction didn't make to a key it read, so it has to run again
+            if runs.get() == 1 {
+                tree.insert(b"key", b"2").map_err(abort)?;
+            }
+            views[0].insert(b"key", &[seen.unwrap_or_default()[0] + 1])?;
+            Ok(())
+        }).unwrap();
+        assert_eq!(runs.get(), 2);
+        assert_eq!(tree.get(b"key").unwrap(), Some(b"3".to_vec()));
+
+        let db = crate::Database::with_backend(backend, bevy_reflect::TypeRegistry::new()).unwrap();
+        let id = crate::ItemId::from_u128(1);
+        let item = crate::ItemData { type_name: "test".into(), data: "1".into() };
+        db.insert_item_data(id, &item).unwrap();
+        let read = db.transaction(|tx| {
+            let read = db.get_item_data(id).map_err(abort)?;
+            tx.insert_item_data(id, &crate::ItemData { data: "2".into(), ..read.clone() })?;
+            Ok(read)
+        }).unwrap();
+        assert_eq!(read, item);
+        assert_eq!(db.get_item_data(id).unwrap().data, "2");
+    }
+
+    #[test]
+    fn memory_transaction_watch() {
+        let backend = MemoryBackend::new();
+        let tree = backend.open_tree("tree").unwrap();
+        let mut watcher = block_on_stream(tree.watch_prefix(b""));
+        backend.transaction(&["tree"], &|views| {
+            views[0].remove(b"missing")?;
+            views[0].insert(b"key", b"1")?;
+            Ok(())
+        }).unwrap();
+        tree.remove(b"missing").unwrap();
+        tree.remove(b"key").unwrap();
+        assert_eq!(watcher.next(), Some(WatchEvent::Insert(b"key".to_vec(), b"1".to_vec())));
+        assert_eq!(watcher.next(), Some(WatchEvent::Remove(b"key".to_vec())));
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_backend() {
+        use crate::{Database, ItemData, ItemId, EventData, EventId, test::test_date};
+        let sled = sled::Config::new().temporary(true).open().unwrap();
+        let db = Database::with_backend(SledBackend::from(sled), bevy_reflect::TypeRegistry::new()).unwrap();
+        let date = test_date();
+        let id = ItemId::from_u128(1);
+        let res: Result<(), DbError> = db.transaction(|tx| {
+            tx.insert_item_data(id, &ItemData { type_name: "test".into(), data: "1".into() })?;
+            Err(abort(DbError::NoData))
+        });
+        assert!(matches!(res, Err(DbError::NoData)));
+        assert!(db.items.is_empty());
+        db.insert_item_data(id, &ItemData { type_name: "test".into(), data: "1".into() }).unwrap();
+        db.add_event(&EventData { type_name: "test".into(), data: "2".into(), date, time: None }).unwrap();
+        let key = db.add_event(&EventData { type_name: "test".into(), data: "3".into(), date, time: None }).unwrap();
+        assert_eq!(key, EventId::date_key(date).with_id(2));
+        assert_eq!(db.items_of_type("test").map(Result::unwrap).collect::<Vec<_>>(), vec![id]);
+        assert_eq!(db.events_on(date).count(), 2);
+    }
+}
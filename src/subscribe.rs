@@ -0,0 +1,186 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures::{Stream, StreamExt};
+use crate::*;
+use crate::history::{Operation, Revision};
+use crate::storage::{WatchEvent, Watcher};
+
+/// Something written to the database, as seen by a [`Subscription`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    ItemAdded { id: ItemId, type_name: String },
+    ItemUpdated { id: ItemId, type_name: String },
+    ItemRemoved { id: ItemId, type_name: String },
+    /// also sent when [`Database::migrate`] or [`Database::import`] rewrite an event
+    EventAppended { id: EventId, type_name: String },
+}
+
+impl Change {
+    pub fn type_name(&self) -> &str {
+        match self {
+            Change::ItemAdded { type_name, .. }
+            | Change::ItemUpdated { type_name, .. }
+            | Change::ItemRemoved { type_name, .. }
+            | Change::EventAppended { type_name, .. } => type_name,
+        }
+    }
+}
+
+/// Which changes a [`Subscription`] yields, [`ChangeFilter::default`] lets everything through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeFilter {
+    items: bool,
+    events: bool,
+    item: Option<ItemId>,
+    type_name: Option<String>,
+}
+
+impl Default for ChangeFilter {
+    fn default() -> Self {
+        ChangeFilter::all()
+    }
+}
+
+impl ChangeFilter {
+    pub fn all() -> ChangeFilter {
+        ChangeFilter { items: true, events: true, item: None, type_name: None }
+    }
+
+    pub fn items() -> ChangeFilter {
+        ChangeFilter { events: false, ..ChangeFilter::all() }
+    }
+
+    pub fn events() -> ChangeFilter {
+        ChangeFilter { items: false, ..ChangeFilter::all() }
+    }
+
+    /// only changes to the item `id`
+    pub fn item(id: ItemId) -> ChangeFilter {
+        ChangeFilter { item: Some(id), ..ChangeFilter::items() }
+    }
+
+    /// only changes to records of `type_name`, as it is stored
+    pub fn of_type(mut self, type_name: &str) -> ChangeFilter {
+        self.type_name = Some(type_name.to_string());
+        self
+    }
+
+    fn wants(&self, type_name: &str) -> bool {
+        self.type_name.is_none() || self.type_name.as_deref() == Some(type_name)
+    }
+
+    // history revisions are stored under the item id followed by the revision number,
+    // the bare id only holds the latest revision number
+    fn item_change(&self, event: WatchEvent) -> Result<Option<Change>, DbError> {
+        let WatchEvent::Insert(key, value) = event else {return Ok(None)};
+        if key.len() != std::mem::size_of::<Uuid>() + std::mem::size_of::<u64>() {return Ok(None)}
+        let id = ItemId::try_from(&key[..std::mem::size_of::<Uuid>()])?;
+        let revision: Revision = ron::de::from_bytes(&value)?;
+        if !self.wants(&revision.type_name) {return Ok(None)}
+        let type_name = revision.type_name;
+        Ok(Some(match revision.operation {
+            Operation::Insert => Change::ItemAdded { id, type_name },
+            Operation::Update => Change::ItemUpdated { id, type_name },
+            Operation::Remove => Change::ItemRemoved { id, type_name },
+        }))
+    }
+
+    // the type tree gets the event's type name in the same transaction as the event, item keys are longer
+    fn event_change(&self, event: WatchEvent) -> Result<Option<Change>, DbError> {
+        let WatchEvent::Insert(key, value) = event else {return Ok(None)};
+        if key.len() != std::mem::size_of::<u64>() {return Ok(None)}
+        let type_name = String::from_utf8(value)?;
+        if !self.wants(&type_name) {return Ok(None)}
+        Ok(Some(Change::EventAppended { id: EventId::try_from(&key[..])?, type_name }))
+    }
+}
+
+/// The changes made after [`Database::subscribe`] was called, as a [`Stream`];
+/// item changes and event changes each arrive in order but not in order with each other
+pub struct Subscription {
+    filter: ChangeFilter,
+    items: Option<Watcher>,
+    events: Option<Watcher>,
+}
+
+impl Subscription {
+    /// waits on the current thread for each change instead
+    pub fn blocking(self) -> futures::executor::BlockingStream<Subscription> {
+        futures::executor::block_on_stream(self)
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Result<Change, DbError>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        while let Some(watcher) = &mut this.items {
+            match watcher.poll_next_unpin(cx) {
+                Poll::Ready(Some(event)) => if let Some(change) = this.filter.item_change(event).transpose() {
+                    return Poll::Ready(Some(change));
+                },
+                Poll::Ready(None) => this.items = None,
+                Poll::Pending => break,
+            }
+        }
+        while let Some(watcher) = &mut this.events {
+            match watcher.poll_next_unpin(cx) {
+                Poll::Ready(Some(event)) => if let Some(change) = this.filter.event_change(event).transpose() {
+                    return Poll::Ready(Some(change));
+                },
+                Poll::Ready(None) => this.events = None,
+                Poll::Pending => break,
+            }
+        }
+        if this.items.is_none() && this.events.is_none() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Database {
+    /// starts listening for changes that pass `filter`, so a server can push them to its clients
+    pub fn subscribe(&self, filter: ChangeFilter) -> Subscription {
+        let items = filter.items.then(|| match filter.item {
+            Some(id) => self.history.watch_prefix(id),
+            None => self.history.watch_prefix(b""),
+        });
+        let events = filter.events.then(|| self.type_tree.watch_prefix(b""));
+        Subscription { filter, items, events }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{test_db, test_date};
+    #[test]
+    fn subscribe() {
+        use crate::{ItemData, ItemId, EventData, subscribe::{Change, ChangeFilter}};
+        let db = test_db();
+        let (id, other) = (ItemId::from_u128(1), ItemId::from_u128(2));
+        let mut all = db.subscribe(ChangeFilter::default()).blocking();
+        let mut one = db.subscribe(ChangeFilter::item(id)).blocking();
+        let mut crates = db.subscribe(ChangeFilter::events().of_type("Crate")).blocking();
+        let item = |data: &str| ItemData { type_name: "Pot".into(), data: data.into() };
+        db.insert_item_data(other, &item("(1)")).unwrap();
+        db.insert_item_data(id, &item("(1)")).unwrap();
+        db.insert_item_data(id, &item("(2)")).unwrap();
+        db.remove(id).unwrap();
+        let event = |type_name: &str| EventData { type_name: type_name.into(), data: "()".into(), date: test_date(), time: None };
+        db.add_event(&event("Tray")).unwrap();
+        let key = db.add_event(&event("Crate")).unwrap();
+        let changes: Vec<_> = all.by_ref().take(6).map(Result::unwrap).collect();
+        assert_eq!(changes[..4], [
+            Change::ItemAdded { id: other, type_name: "Pot".into() },
+            Change::ItemAdded { id, type_name: "Pot".into() },
+            Change::ItemUpdated { id, type_name: "Pot".into() },
+            Change::ItemRemoved { id, type_name: "Pot".into() },
+        ]);
+        assert_eq!(changes[5], Change::EventAppended { id: key, type_name: "Crate".into() });
+        let changes: Vec<_> = one.by_ref().take(3).map(Result::unwrap).collect();
+        assert!(changes.iter().all(|change| matches!(change, Change::ItemAdded { id: changed, .. } | Change::ItemUpdated { id: changed, .. } | Change::ItemRemoved { id: changed, .. } if *changed == id)));
+        assert_eq!(crates.next().unwrap().unwrap(), Change::EventAppended { id: key, type_name: "Crate".into() });
+    }
+}
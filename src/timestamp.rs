@@ -0,0 +1,171 @@
+use serde::{Serialize, Deserialize};
+use crate::date::{Date, DateError};
+
+const SECONDS_IN_DAY: u32 = 24 * 60 * 60;
+
+/// A [`Date`] and the second of that day, always in UTC so events from clients in
+/// different timezones sort together; convert with [`Timestamp::to_datetime`] to show one.
+/// The date of a timestamp can be a day off from [`Date::today`] in a local timezone
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    date: Date,
+    seconds: u32,
+}
+
+impl Timestamp {
+    pub fn new(date: Date, hour: u32, minute: u32, second: u32) -> Result<Timestamp, DateError> {
+        if hour >= 24 || minute >= 60 || second >= 60 {
+            return Err(DateError::BadTime { hour, minute, second });
+        }
+        Ok(Timestamp { date, seconds: (hour * 60 + minute) * 60 + second })
+    }
+
+    /// `seconds` after midnight on `date`
+    pub fn from_seconds(date: Date, seconds: u32) -> Result<Timestamp, DateError> {
+        Timestamp::new(date, seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+
+    pub fn now() -> Timestamp {
+        Timestamp::try_from(chrono::Utc::now()).expect("the current year to fit in a Date")
+    }
+
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    /// seconds after midnight
+    pub fn seconds(&self) -> u32 {
+        self.seconds
+    }
+
+    pub fn hour(&self) -> u32 {
+        self.seconds / 3600
+    }
+
+    pub fn minute(&self) -> u32 {
+        self.seconds / 60 % 60
+    }
+
+    pub fn second(&self) -> u32 {
+        self.seconds % 60
+    }
+
+    /// the same moment in `tz`
+    pub fn to_datetime<Tz: chrono::TimeZone>(&self, tz: &Tz) -> chrono::DateTime<Tz> {
+        tz.from_utc_datetime(&(*self).into())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de> {
+        // what Serialize writes, checked through from_seconds so a stored time can't be past the end of its day
+        #[derive(Deserialize)]
+        #[serde(rename = "Timestamp")]
+        struct Fields {
+            date: Date,
+            seconds: u32,
+        }
+        let Fields { date, seconds } = Fields::deserialize(deserializer)?;
+        Timestamp::from_seconds(date, seconds).map_err(serde::de::Error::custom)
+    }
+}
+
+impl std::ops::Add<i64> for Timestamp {
+    type Output = Timestamp;
+    /// moves by `seconds`, across days if need be
+    fn add(self, seconds: i64) -> Timestamp {
+        let seconds = self.seconds as i64 + seconds;
+        Timestamp {
+            date: self.date + seconds.div_euclid(SECONDS_IN_DAY as i64) as i32,
+            seconds: seconds.rem_euclid(SECONDS_IN_DAY as i64) as u32,
+        }
+    }
+}
+
+impl From<Timestamp> for chrono::NaiveDateTime {
+    fn from(time: Timestamp) -> Self {
+        chrono::NaiveDate::from(time.date).and_hms_opt(time.hour(), time.minute(), time.second())
+            .expect("a Timestamp to always hold a valid time")
+    }
+}
+
+/// drops anything below a second, a leap second becomes the second before it
+impl TryFrom<chrono::NaiveDateTime> for Timestamp {
+    type Error = DateError;
+    fn try_from(time: chrono::NaiveDateTime) -> Result<Self, Self::Error> {
+        use chrono::Timelike;
+        Timestamp::from_seconds(Date::try_from(time.date())?, time.num_seconds_from_midnight())
+    }
+}
+
+/// converts to UTC first
+impl<Tz: chrono::TimeZone> TryFrom<chrono::DateTime<Tz>> for Timestamp {
+    type Error = DateError;
+    fn try_from(time: chrono::DateTime<Tz>) -> Result<Self, Self::Error> {
+        Timestamp::try_from(time.naive_utc())
+    }
+}
+
+/// ISO 8601 in UTC, `2022-09-01T06:05:00Z`
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}T{:02}:{:02}:{:02}Z", self.date, self.hour(), self.minute(), self.second())
+    }
+}
+
+impl std::str::FromStr for Timestamp {
+    type Err = DateError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((date, time)) = s.split_once('T') else {return Err(DateError::Parse("No Time Seg"))};
+        let Some(time) = time.strip_suffix('Z') else {return Err(DateError::Parse("Time is not in UTC"))};
+        let mut segs = time.split(':').map(|seg| seg.parse::<u32>());
+        let (Some(Ok(hour)), Some(Ok(minute)), Some(Ok(second)), None) = (segs.next(), segs.next(), segs.next(), segs.next()) else {
+            return Err(DateError::Parse("Failed to parse time"));
+        };
+        Timestamp::new(Date::from_str(date)?, hour, minute, second)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use crate::date::DateError;
+    use std::str::FromStr;
+    #[test]
+    fn timestamp_formats() {
+        let date = Date::new_ymd(2022, 9, 1);
+        let time = Timestamp::new(date, 6, 5, 0).unwrap();
+        assert_eq!(time.seconds(), 6 * 3600 + 5 * 60);
+        assert_eq!(time.to_string(), "2022-09-01T06:05:00Z");
+        assert_eq!(Timestamp::from_str("2022-09-01T06:05:00Z"), Ok(time));
+        assert_eq!(Timestamp::from_str("2022-09-01T06:05:00"), Err(DateError::Parse("Time is not in UTC")));
+        assert_eq!(Timestamp::new(date, 24, 0, 0), Err(DateError::BadTime { hour: 24, minute: 0, second: 0 }));
+        assert_eq!(Timestamp::from_seconds(date, 86400), Err(DateError::BadTime { hour: 24, minute: 0, second: 0 }));
+        assert_eq!(time + -(6 * 3600 + 5 * 60 + 1), Timestamp::new(Date::new_ymd(2022, 8, 31), 23, 59, 59).unwrap());
+
+        let naive = chrono::NaiveDateTime::from(time);
+        assert_eq!(Timestamp::try_from(naive), Ok(time));
+        let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        let local = time.to_datetime(&tz);
+        assert_eq!(local.to_rfc3339(), "2022-09-01T08:05:00+02:00");
+        assert_eq!(Timestamp::try_from(local), Ok(time));
+        assert!(Timestamp::now().date() >= date);
+    }
+
+    #[test]
+    fn timestamp_deserialize_checks_seconds() {
+        let date = Date::new_ymd(2022, 9, 1);
+        let time = Timestamp::new(date, 23, 59, 59).unwrap();
+        let event = EventData { type_name: "test".into(), data: "()".into(), date, time: Some(time) };
+        let body = ron::to_string(&event).unwrap();
+        assert_eq!(ron::from_str::<EventData>(&body).unwrap().time, Some(time));
+        let late = body.replace("seconds:86399", "seconds:86400");
+        assert_ne!(late, body);
+        assert!(ron::from_str::<EventData>(&late).is_err());
+        // one that got past anyway stays on its own day
+        let late = Timestamp { date, seconds: 3 * 86400 };
+        assert_eq!(EventId::time_key(late).date_from_key(), date);
+    }
+}
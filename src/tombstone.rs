@@ -0,0 +1,89 @@
+use serde::{Serialize, Deserialize};
+use crate::*;
+use crate::transaction::{TxResult, abort};
+
+/// An item that was removed with [`Database::remove`] and can still be brought back with [`Database::restore`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub item: ItemData,
+    pub deleted: Date,
+    /// the schema version `item` was written with
+    #[serde(default)]
+    pub schema: u32,
+}
+
+impl<'a> DbTransaction<'a> {
+    pub(crate) fn bury(&self, id: ItemId, item: ItemData, schema: u32) -> TxResult<()> {
+        let tombstone = Tombstone { item, deleted: Date::today(&chrono::Utc), schema };
+        self.tombstones.insert(&id.as_bytes()[..], ron::to_string(&tombstone).map_err(abort)?.as_str())?;
+        Ok(())
+    }
+
+    /// puts a removed item back, failing if there is nothing to restore or the id has been reused
+    pub fn restore(&self, id: ItemId) -> TxResult<()> {
+        let Some(raw) = self.tombstones.remove(&id.as_bytes()[..])? else {return Err(abort(DbError::NotDeleted(id)))};
+        if self.type_tree.get(&id.as_bytes()[..])?.is_some() {
+            return Err(abort(DbError::AlreadyExists(id)));
+        }
+        let tombstone: Tombstone = ron::de::from_bytes(&raw).map_err(abort)?;
+        self.insert_item_data(id, &tombstone.item)?;
+        // the data is still what it was when removed, leave it for Database::migrate
+        self.set_schema(&id.as_bytes()[..], tombstone.schema)?;
+        Ok(())
+    }
+}
+
+impl Database {
+    pub fn restore(&self, id: ItemId) -> Result<(), DbError> {
+        self.transaction(|tx| tx.restore(id))
+    }
+
+    /// every removed item that has not been purged yet
+    pub fn list_deleted(&self) -> Result<Vec<(ItemId, Tombstone)>, DbError> {
+        let mut deleted = Vec::new();
+        for res in self.tombstones.iter() {
+            let (key, data) = res?;
+            deleted.push((ItemId::try_from(key.as_ref())?, ron::de::from_bytes(&data)?));
+        }
+        Ok(deleted)
+    }
+
+    /// permanently drops every item removed before `date`, returns how many were dropped
+    pub fn purge_older_than(&self, date: Date) -> Result<usize, DbError> {
+        let mut purged = 0;
+        for (id, tombstone) in self.list_deleted()? {
+            if tombstone.deleted < date {
+                self.tombstones.remove(id)?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::test_db;
+    #[test]
+    fn soft_delete() {
+        use crate::{ItemData, ItemId, DbError};
+        let db = test_db();
+        let id = ItemId::from_u128(1);
+        let item = ItemData { type_name: "crate".into(), data: "First".into() };
+        db.insert_item_data(id, &item).unwrap();
+        db.remove(id).unwrap();
+        assert!(db.get_item_data(id).is_err());
+        let deleted = db.list_deleted().unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].0, id);
+        assert_eq!(deleted[0].1.item, item);
+        db.restore(id).unwrap();
+        assert_eq!(db.get_item_data(id).unwrap(), item);
+        assert!(matches!(db.restore(id), Err(DbError::NotDeleted(_))));
+        db.remove(id).unwrap();
+        let deleted_on = db.list_deleted().unwrap()[0].1.deleted;
+        assert_eq!(db.purge_older_than(deleted_on).unwrap(), 0);
+        assert_eq!(db.purge_older_than(deleted_on.next()).unwrap(), 1);
+        assert!(db.list_deleted().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,267 @@
+use bevy_reflect::prelude::*;
+use serde::Serialize;
+use crate::*;
+use crate::index;
+use crate::storage::TxTree;
+use crate::history;
+use crate::integrity::{self, Integrity, RemovePolicy};
+
+/// Why a transaction stopped, each [`StorageBackend`](crate::storage::StorageBackend) turns its own errors into these
+#[derive(Debug)]
+pub enum TxError {
+    /// the closure gave up, the transaction returns the error and writes nothing
+    Abort(DbError),
+    /// another writer got in the way, the backend runs the closure again
+    Conflict,
+    /// the backend failed to read or write
+    Storage(DbError),
+}
+
+pub type TxResult<T> = Result<T, TxError>;
+
+/// turns any error the database knows about into a transaction abort so it can be used with `?`
+pub fn abort(e: impl Into<DbError>) -> TxError {
+    TxError::Abort(e.into())
+}
+
+/// A view of every tree in the [`Database`] inside one backend transaction;
+/// nothing written through it is visible until the closure passed to [`Database::transaction`] returns `Ok`
+pub struct DbTransaction<'a> {
+    pub(crate) type_registry: &'a bevy_reflect::TypeRegistry,
+    pub(crate) db: TxTree<'a>,
+    pub(crate) items: TxTree<'a>,
+    pub(crate) events: TxTree<'a>,
+    pub(crate) type_tree: TxTree<'a>,
+    pub(crate) type_index: TxTree<'a>,
+    pub(crate) field_index: TxTree<'a>,
+    pub(crate) dependents: TxTree<'a>,
+    pub(crate) history: TxTree<'a>,
+    pub(crate) tombstones: TxTree<'a>,
+    pub(crate) versions: TxTree<'a>,
+    pub(crate) schemas: TxTree<'a>,
+    pub(crate) integrity: Integrity,
+    pub(crate) actor: Option<&'a str>,
+    pub(crate) migrations: &'a crate::migration::Migrations,
+    pub(crate) encoding: crate::encoding::Encoding,
+}
+
+impl<'a> DbTransaction<'a> {
+    pub fn next_event_id(&self, date: Date) -> TxResult<EventId> {
+        let key = EventId::date_key(date);
+        Ok(key.with_id(self.db.increment(key)?))
+    }
+
+    /// the key for the next event at `time`, counted with the events of the day without a time
+    pub fn next_timed_event_id(&self, time: Timestamp) -> TxResult<EventId> {
+        let val = self.db.increment(EventId::date_key(time.date()))?;
+        if val > EventId::MAX_TIMED_VAL {
+            return Err(abort(DbError::TooManyEvents(time.date())));
+        }
+        Ok(EventId::time_key(time).with_id(val))
+    }
+
+    pub fn add_event(&self, event: &EventData) -> TxResult<EventId> {
+        let key = match event.time {
+            Some(time) if time.date() != event.date => return Err(abort(DbError::EventTimeMismatch(event.date, time))),
+            Some(time) => self.next_timed_event_id(time)?,
+            None => self.next_event_id(event.date)?,
+        };
+        self.type_tree.insert(key.as_ref(), event.type_name.as_str())?;
+        self.events.insert(key.as_ref(), self.encode(&event.type_name, &event.data)?)?;
+        self.set_schema(key.as_ref(), self.migrations.version(&event.type_name))?;
+        Ok(key)
+    }
+
+    pub fn add_event_obj(&self, event: &dyn Reflect, date: Date) -> TxResult<EventId> {
+        let (type_name, data) = serialize_obj(self.type_registry, event).map_err(abort)?;
+        self.add_event(&EventData { type_name: type_name.to_string(), data, date, time: None })
+    }
+
+    pub fn add_event_obj_at(&self, event: &dyn Reflect, time: Timestamp) -> TxResult<EventId> {
+        let (type_name, data) = serialize_obj(self.type_registry, event).map_err(abort)?;
+        self.add_event(&EventData { type_name: type_name.to_string(), data, date: time.date(), time: Some(time) })
+    }
+
+    pub fn add_item(&self, item: &ItemData) -> TxResult<ItemId> {
+        let id = ItemId::new();
+        self.insert_item_data(id, item)?;
+        Ok(id)
+    }
+
+    /// stores `item` under its registered name, so [`Database::get_item`] and the indexes agree on it
+    pub fn insert_item<T: Serialize + 'static>(&self, id: ItemId, item: &T) -> TxResult<()> {
+        let data = ron::to_string(item).map_err(abort)?;
+        self.insert_item_data(id, &ItemData { type_name: crate::stored_name::<T>(self.type_registry).to_string(), data })
+    }
+
+    pub fn insert_item_data(&self, id: ItemId, item: &ItemData) -> TxResult<()> {
+        let new = index::item_indexes(self.type_registry, self.migrations, item.type_name.as_bytes(), item.data.as_bytes(), id).map_err(abort)?;
+        if self.integrity.check_dependencies {
+            for &dep in new.dependencies.iter() {
+                if self.items.get(&dep.as_bytes()[..])?.is_none() {
+                    return Err(abort(DbError::MissingDependency(id, dep)));
+                }
+            }
+        }
+        let old_type = self.type_tree.insert(&id.as_bytes()[..], item.type_name.as_str())?;
+        let old_data = self.items.insert(&id.as_bytes()[..], self.encode(&item.type_name, &item.data)?)?;
+        self.set_schema(&id.as_bytes()[..], self.migrations.version(&item.type_name))?;
+        self.bump_version(id)?;
+        let operation = if old_type.is_some() {history::Operation::Update} else {history::Operation::Insert};
+        let previous = self.previous(&old_type, &old_data)?;
+        self.remove_from_indexes(id, &old_type, previous.as_ref())?;
+        history::record(self.history, id, operation, self.actor, &item.type_name, previous)?;
+        self.type_index.insert(index::type_key(item.type_name.as_bytes(), id), &b""[..])?;
+        for key in new.fields {
+            self.field_index.insert(key, &b""[..])?;
+        }
+        for dep in new.dependencies {
+            integrity::add_dependent(self.dependents, dep, id)?;
+        }
+        Ok(())
+    }
+
+    /// stores `item` under its own id, or a new one if it has none yet, with the name it is registered under
+    pub fn put<T: Item>(&self, item: &T) -> TxResult<ItemId> {
+        let id = if item.id().is_nil() {ItemId::new()} else {item.id()};
+        let (type_name, data) = serialize_obj(self.type_registry, item.as_reflect()).map_err(abort)?;
+        self.insert_item_data(id, &ItemData { type_name: type_name.to_string(), data })?;
+        Ok(id)
+    }
+
+    /// the item under `id` deserialized through the type registry
+    pub fn get_obj(&self, id: ItemId) -> TxResult<Box<dyn Reflect>> {
+        let Some(type_name) = self.type_tree.get(&id.as_bytes()[..])? else {return Err(abort(DbError::NoTypeName))};
+        let type_name = String::from_utf8(type_name).map_err(abort)?;
+        let Some(data) = self.items.get(&id.as_bytes()[..])? else {return Err(abort(DbError::NoData))};
+        self.encoding.deserialize(self.type_registry, self.migrations.resolve(&type_name), &data)
+            .map_err(crate::encoding::record_error(&type_name, id.into())).map_err(abort)
+    }
+
+    pub fn get<T: Item>(&self, id: ItemId) -> TxResult<T> {
+        take_item(self.get_obj(id)?, id).map_err(abort)
+    }
+
+    /// moves an item to the tombstones so it can be restored later,
+    /// what happens to items that depend on it is up to [`Integrity::on_remove`]
+    pub fn remove(&self, id: ItemId) -> TxResult<()> {
+        let dependents = self.dependents_of(id)?;
+        if !dependents.is_empty() {
+            match self.integrity.on_remove {
+                RemovePolicy::Allow => {},
+                RemovePolicy::Restrict => return Err(abort(DbError::HasDependents(id, dependents))),
+                RemovePolicy::Cascade => {
+                    // drop our entry first so a dependency cycle can't bring us back here
+                    self.dependents.remove(&id.as_bytes()[..])?;
+                    for dependent in dependents {
+                        self.remove(dependent)?;
+                    }
+                }
+            }
+        }
+        let old_data = self.items.remove(&id.as_bytes()[..])?;
+        let old_type = self.type_tree.remove(&id.as_bytes()[..])?;
+        let schema = self.set_schema(&id.as_bytes()[..], 0)?;
+        let previous = self.previous(&old_type, &old_data)?;
+        self.remove_from_indexes(id, &old_type, previous.as_ref())?;
+        if let Some(old_type) = &old_type {
+            self.bump_version(id)?;
+            history::record(self.history, id, history::Operation::Remove, self.actor, std::str::from_utf8(old_type).map_err(abort)?, previous.clone())?;
+        }
+        if let Some(item) = previous {
+            self.bury(id, item, schema)?;
+        }
+        Ok(())
+    }
+
+    /// every item whose [`Item::dependencies`] contains `id`
+    pub fn dependents_of(&self, id: ItemId) -> TxResult<Vec<ItemId>> {
+        Ok(integrity::parse_dependents(self.dependents.get(&id.as_bytes()[..])?.as_deref()))
+    }
+
+    /// `data` of `type_name` as it is written to the items and events trees
+    pub(crate) fn encode(&self, type_name: &str, data: &str) -> TxResult<Vec<u8>> {
+        self.encoding.encode_ron(self.type_registry, self.migrations.resolve(type_name), data).map_err(abort)
+    }
+
+    /// stored `data` of `type_name` back as ron
+    pub(crate) fn decode(&self, type_name: &str, data: &[u8]) -> TxResult<String> {
+        self.encoding.to_ron(self.type_registry, self.migrations.resolve(type_name), data).map_err(abort)
+    }
+
+    /// what a write replaced, with the data turned back into ron
+    fn previous(&self, old_type: &Option<Vec<u8>>, old_data: &Option<Vec<u8>>) -> TxResult<Option<ItemData>> {
+        let (Some(type_name), Some(data)) = (old_type, old_data) else {return Ok(None)};
+        let type_name = String::from_utf8(type_name.to_vec()).map_err(abort)?;
+        let data = self.decode(&type_name, data)?;
+        Ok(Some(ItemData { type_name, data }))
+    }
+
+    fn remove_from_indexes(&self, id: ItemId, old_type: &Option<Vec<u8>>, previous: Option<&ItemData>) -> TxResult<()> {
+        let Some(old_type) = old_type else {return Ok(())};
+        self.type_index.remove(index::type_key(old_type, id))?;
+        if let Some(previous) = previous {
+            // an old record that no longer deserializes can't have its index entries found, it just gets replaced
+            let old = index::item_indexes(self.type_registry, self.migrations, old_type, previous.data.as_bytes(), id).unwrap_or_default();
+            for key in old.fields {
+                self.field_index.remove(key)?;
+            }
+            for dep in old.dependencies {
+                integrity::remove_dependent(self.dependents, dep, id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// serializes `obj` to ron using its registered [`ReflectSerialize`]
+pub(crate) fn serialize_obj(type_registry: &bevy_reflect::TypeRegistry, obj: &dyn Reflect) -> Result<(&'static str, String), DbError> {
+    let registration = match type_registry.get(obj.type_id()) {
+        Some(r) => r,
+        None => {return Err(DbError::TypeNotRegistered(obj.type_name().to_string()))}
+    };
+    let ser = match registration.data::<ReflectSerialize>() {
+        Some(s) => s,
+        None => return Err(DbError::NoReflectSerialize(registration.type_name())),
+    };
+    let ser = match ser.get_serializable(obj.as_reflect()) {
+        bevy_reflect::serde::Serializable::Owned(s) => ron::to_string(&s),
+        bevy_reflect::serde::Serializable::Borrowed(s) => ron::to_string(s),
+    }?;
+    Ok((registration.type_name(), ser))
+}
+
+/// `obj` as the `T` stored under `id`, or [`DbError::TypeMissMatch`] with `obj` handed back
+pub(crate) fn take_item<T: Item>(obj: Box<dyn Reflect>, id: ItemId) -> Result<T, DbError> {
+    let mut item = obj.take::<T>().map_err(DbError::TypeMissMatch)?;
+    item.set_id(id);
+    Ok(item)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use crate::test::{test_db, test_date};
+    #[test]
+    fn transaction_is_atomic() {
+        use crate::{ItemData, ItemId, EventData, DbError, transaction::abort};
+        let db = test_db();
+        let date = test_date();
+        let item = ItemData { type_name: "test".into(), data: "1".into() };
+        let id = ItemId::from_u128(1);
+        let res: Result<(), DbError> = db.transaction(|tx| {
+            tx.insert_item_data(id, &item)?;
+            tx.add_event(&EventData { type_name: "test".into(), data: "2".into(), date, time: None })?;
+            Err(abort(DbError::NoData))
+        });
+        assert!(matches!(res, Err(DbError::NoData)));
+        assert!(matches!(db.get_item_data(id), Err(DbError::NoTypeName)));
+        assert_eq!(db.events_on(date).count(), 0);
+        let key = db.transaction(|tx| {
+            tx.insert_item_data(id, &item)?;
+            tx.add_event(&EventData { type_name: "test".into(), data: "2".into(), date, time: None })
+        }).unwrap();
+        assert_eq!(key, EventId::date_key(date).with_id(1));
+        assert_eq!(db.get_item_data(id).unwrap().data, "1");
+    }
+}
@@ -0,0 +1,78 @@
+use crate::*;
+use crate::transaction::{TxResult, abort};
+
+// the versions tree maps an item to a big endian counter bumped by every write and removal,
+// it is never reset so a version number is never reused for the same id
+
+fn parse_version(raw: Option<&[u8]>) -> u64 {
+    raw.and_then(|raw| raw.try_into().ok()).map(u64::from_be_bytes).unwrap_or(0)
+}
+
+impl<'a> DbTransaction<'a> {
+    /// the number of times `id` has been written or removed, 0 if it never has
+    pub fn item_version(&self, id: ItemId) -> TxResult<u64> {
+        Ok(parse_version(self.versions.get(&id.as_bytes()[..])?.as_deref()))
+    }
+
+    pub(crate) fn bump_version(&self, id: ItemId) -> TxResult<u64> {
+        let version = self.item_version(id)? + 1;
+        self.versions.insert(&id.as_bytes()[..], &version.to_be_bytes()[..])?;
+        Ok(version)
+    }
+
+    /// writes `item` only if `id` is still at `expected_version`, returns the new version
+    pub fn compare_and_swap_item(&self, id: ItemId, expected_version: u64, item: &ItemData) -> TxResult<u64> {
+        let found = self.item_version(id)?;
+        if found != expected_version {
+            return Err(abort(DbError::VersionConflict(id, expected_version, found)));
+        }
+        self.insert_item_data(id, item)?;
+        self.item_version(id)
+    }
+}
+
+impl Database {
+    pub fn item_version(&self, id: ItemId) -> Result<u64, DbError> {
+        Ok(parse_version(self.versions.get(id)?.as_deref()))
+    }
+
+    /// reads an item and the version it is at in one go,
+    /// hand the version back to [`Database::compare_and_swap_item`] to save an edit
+    pub fn get_item_data_versioned(&self, id: ItemId) -> Result<(ItemData, u64), DbError> {
+        self.transaction(|tx| {
+            let (Some(type_name), Some(data)) = (tx.type_tree.get(&id.as_bytes()[..])?, tx.items.get(&id.as_bytes()[..])?) else {
+                return Err(abort(DbError::NoData));
+            };
+            let type_name = String::from_utf8(type_name.to_vec()).map_err(abort)?;
+            let item = ItemData { data: tx.decode(&type_name, &data)?, type_name };
+            Ok((item, tx.item_version(id)?))
+        })
+    }
+
+    /// saves `item` unless someone else wrote `id` since `expected_version` was read,
+    /// in which case it fails with [`DbError::VersionConflict`] and the caller should reload
+    pub fn compare_and_swap_item(&self, id: ItemId, expected_version: u64, item: &ItemData) -> Result<u64, DbError> {
+        self.transaction(|tx| tx.compare_and_swap_item(id, expected_version, item))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::test_db;
+    #[test]
+    fn compare_and_swap() {
+        use crate::{ItemData, ItemId, DbError};
+        let db = test_db();
+        let id = ItemId::from_u128(1);
+        let item = ItemData { type_name: "crate".into(), data: "First".into() };
+        assert_eq!(db.compare_and_swap_item(id, 0, &item).unwrap(), 1);
+        let (read, version) = db.get_item_data_versioned(id).unwrap();
+        assert_eq!((read, version), (item.clone(), 1));
+        let edit = ItemData { type_name: "crate".into(), data: "Second".into() };
+        assert_eq!(db.compare_and_swap_item(id, version, &edit).unwrap(), 2);
+        assert!(matches!(db.compare_and_swap_item(id, version, &item), Err(DbError::VersionConflict(_, 1, 2))));
+        assert_eq!(db.get_item_data(id).unwrap(), edit);
+        db.remove(id).unwrap();
+        assert_eq!(db.item_version(id).unwrap(), 3);
+    }
+}
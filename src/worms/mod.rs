@@ -1,4 +1,5 @@
 use crate::*;
+use crate::items::ReflectToItem;
 use enum_utils::FromStr;
 use serde::{Deserialize, Serialize};
 use strum::{EnumIter, IntoStaticStr};
@@ -7,6 +8,20 @@ pub fn register_types(reg: &mut bevy_reflect::TypeRegistry) {
     reg.register::<Gender>();
     reg.register::<WormType>();
     reg.register::<Stage>();
+    reg.register::<Worm>();
+    reg.register::<Brood>();
+    reg.register::<Event>();
+}
+
+pub struct Worms;
+
+impl crate::module::Module for Worms {
+    fn name(&self) -> &'static str {
+        "worms"
+    }
+    fn register_types(&self, type_reg: &mut bevy_reflect::TypeRegistry) {
+        register_types(type_reg);
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Reflect, Clone, Copy, FromStr, EnumIter, IntoStaticStr, FromReflect)]
@@ -67,7 +82,7 @@ impl std::fmt::Display for Stage {
 }
 
 #[derive(Debug, Deserialize, Serialize, Reflect, Clone)]
-#[reflect(Deserialize, Serialize, Default)]
+#[reflect(Deserialize, Serialize, Default, Indexed, ToItem)]
 struct Worm {
     #[serde(skip)]
     id: ItemId,
@@ -121,6 +136,10 @@ impl Item for Worm {
     }
 }
 
+impl Indexed for Worm {
+    const INDEXED_FIELDS: &'static [&'static str] = &["stage"];
+}
+
 #[cfg(feature = "yew")]
 pub mod yew {
     use std::rc::Rc;